@@ -0,0 +1,187 @@
+//! Offline benchmarks of the interpreter and drawer hot paths, without extra dependencies.
+//!
+//!     cargo bench --bench hot_paths -- [filter] [--save <name>] [--baseline <name>]
+//!
+//! With `--save` results are stored in `benches/baselines/<name>.ron`, out of reach of
+//! `cargo clean` and meant to be committed, so that runs on different commits can be
+//! compared with `--baseline`.
+//! Recorded prefixes need `data/endo/dna`, recorded command streams need the
+//! `commands.ron` produced by the interpreter entry point, missing ones are skipped.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use morph_endo::interpreter::dna::{Base, Dna};
+use morph_endo::interpreter::interpreter::{do_step, do_step_cached, Context};
+use morph_endo::interpreter::literals::protect;
+use morph_endo::interpreter::packed::PackedSeq;
+use morph_endo::interpreter::rope::Seq;
+use morph_endo::interpreter::runner::dna_for_task;
+use morph_endo::interpreter::step_cache::StepCache;
+use morph_endo::utils::{read, store_to};
+use morph_endo::{DrawCommand, Drawer};
+
+const SAMPLE_TIME: Duration = Duration::from_secs(2);
+const MAX_SAMPLES: usize = 100;
+const RECORDED_TASKS: [&str; 3] = ["health_check", "repair_guide/initial_0", "repair_guide/topics_1"];
+const RECORDED_STEPS: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BenchResult {
+    samples: usize,
+    median_ns: u128,
+    min_ns: u128,
+}
+
+struct Bencher {
+    filter: Option<String>,
+    results: BTreeMap<String, BenchResult>,
+    baseline: Option<BTreeMap<String, BenchResult>>,
+}
+
+impl Bencher {
+    /// Times `f` until SAMPLE_TIME or MAX_SAMPLES runs are spent, `setup` is not timed.
+    fn run<S, T, F>(&mut self, name: &str, mut setup: S, mut f: F)
+    where
+        S: FnMut() -> T,
+        F: FnMut(T),
+    {
+        if matches!(&self.filter, Some(filter) if !name.contains(filter.as_str())) {
+            return;
+        }
+        // warm up
+        f(setup());
+        let mut samples = Vec::new();
+        let started = Instant::now();
+        while samples.len() < MAX_SAMPLES && started.elapsed() < SAMPLE_TIME {
+            let input = setup();
+            let start = Instant::now();
+            f(input);
+            samples.push(start.elapsed().as_nanos());
+        }
+        samples.sort_unstable();
+        let result = BenchResult { samples: samples.len(), median_ns: samples[samples.len() / 2], min_ns: samples[0] };
+        let change = self.baseline.as_ref()
+            .and_then(|baseline| baseline.get(name))
+            .map(|before| format!("{:+.1}%", (result.median_ns as f64 / before.median_ns as f64 - 1.0) * 100.0))
+            .unwrap_or_default();
+        println!("{:<40} {:>14?} median {:>14?} min {:>4} samples {}", name,
+                 Duration::from_nanos(result.median_ns as u64), Duration::from_nanos(result.min_ns as u64),
+                 result.samples, change);
+        self.results.insert(name.to_string(), result);
+    }
+}
+
+// xorshift, to keep the inputs reproducible
+fn random_bases(len: usize) -> Vec<Base> {
+    let mut state = 0x5eedu64;
+    (0..len).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        [Base::I, Base::C, Base::F, Base::P][(state % 4) as usize]
+    }).collect()
+}
+
+fn results_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("benches").join("baselines").join(format!("{}.ron", name))
+}
+
+fn rope_benches(b: &mut Bencher) {
+    let bases = random_bases(1_000_000);
+    let seq = Seq::from_slice(&bases);
+    b.run("seq/from_slice 1M", || (), |_| { Seq::from_slice(&bases); });
+    b.run("seq/subseq 1M", || (), |_| { seq.subseq(250_000..750_000); });
+    b.run("seq/concat 2x1M", || (), |_| { seq.concat(&seq); });
+    // the way the interpreter splits off a prefix and prepends a replacement
+    b.run("seq/split and rejoin 1M", || (), |_| {
+        seq.subseq(..100).concat(&seq.subseq(100..));
+    });
+    let packed = PackedSeq::from_slice(&bases);
+    b.run("packed/from_slice 1M", || (), |_| { PackedSeq::from_slice(&bases); });
+    b.run("packed/subseq 1M", || (), |_| { packed.subseq(250_001..750_003); });
+    b.run("packed/concat 2x1M", || (), |_| { packed.concat(&packed); });
+    b.run("packed/split and rejoin 1M", || (), |_| {
+        packed.subseq(..101).concat(&packed.subseq(101..));
+    });
+}
+
+fn matching_benches(b: &mut Bencher) {
+    let dna = Dna::from_slice(&random_bases(1_000_000));
+    // random dna never contains a long run of one base, so the whole sequence is scanned
+    let absent = [Base::P; 16];
+    b.run("find_subseq absent 1M", || (), |_| {
+        assert_eq!(dna.find(0, &absent), None);
+    });
+    let short = Dna::from_slice(&random_bases(10_000));
+    b.run("protect l=1 10k", || (), |_| { protect(1, &short); });
+    b.run("protect l=3 10k", || (), |_| { protect(3, &short); });
+}
+
+fn step_benches(b: &mut Bencher) {
+    for task in RECORDED_TASKS {
+        let dna = match dna_for_task("data", task) {
+            Ok(dna) => dna,
+            Err(err) => {
+                println!("do_step {}: skipped, {}", task, err);
+                continue;
+            }
+        };
+        b.run(&format!("do_step x{} {}", RECORDED_STEPS, task), || Context::new(dna.clone()), |mut context| {
+            for _ in 0..RECORDED_STEPS {
+                if do_step(&mut context).is_err() {
+                    break;
+                }
+            }
+        });
+        b.run(&format!("do_step_cached x{} {}", RECORDED_STEPS, task), || Context::new(dna.clone()), |mut context| {
+            let mut cache = StepCache::new();
+            for _ in 0..RECORDED_STEPS {
+                if do_step_cached(&mut context, &mut cache).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+fn drawer_benches(b: &mut Bencher) {
+    for task in RECORDED_TASKS {
+        let commands: Vec<DrawCommand> = match read(Path::new("cache").join(task).join("commands.ron")) {
+            Ok(commands) => commands,
+            Err(err) => {
+                println!("apply_all {}: skipped, {}", task, err);
+                continue;
+            }
+        };
+        b.run(&format!("apply_all {}", task), Drawer::new, |mut drawer| drawer.apply_all(&commands));
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut filter = None;
+    let mut save = None;
+    let mut baseline = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // passed by cargo bench
+            "--bench" => {}
+            "--save" => save = Some(args.next().expect("--save expects a name")),
+            "--baseline" => baseline = Some(args.next().expect("--baseline expects a name")),
+            _ => filter = Some(arg),
+        }
+    }
+    let baseline = baseline.map(|name| read(results_path(&name)).unwrap_or_else(|err| panic!("{}", err)));
+    let mut b = Bencher { filter, results: BTreeMap::new(), baseline };
+    rope_benches(&mut b);
+    matching_benches(&mut b);
+    step_benches(&mut b);
+    drawer_benches(&mut b);
+    if let Some(save) = save {
+        store_to(&b.results, results_path(&save));
+        println!("Stored in {}", results_path(&save).display());
+    }
+}
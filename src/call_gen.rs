@@ -4,6 +4,7 @@
 // Tmp: [Ref(num=0, prot_lvl=0), I, C, I, I, C, I, C, C, I, I, C, P, I, I, C, I, C, C, C, C, C, P, Ref(num=1, prot_lvl=0)]
 
 use crate::interpreter::{template::TItem, pattern::PItem, dna::{Base, Dna}, template, pattern};
+use crate::cli::Args;
 use crate::interpreter::literals::asnat;
 use crate::interpreter::pattern::pattern;
 
@@ -12,10 +13,11 @@ fn search(s: &str) -> PItem {
     PItem::Search { s }
 }
 
-fn call_gen_prefix(offset: i32, len: i32) -> Vec<Base> {
+/// Prefix which makes Endo call the gene at `offset` of length `len`.
+pub fn call_gen_prefix(offset: i32, len: i32) -> Vec<Base> {
+    use Base::*;
     use PItem::*;
     use TItem::*;
-    use Base::*;
     let p = vec![Open,
                  search("IFPICFPPCCC"),
                  Open,
@@ -32,14 +34,49 @@ fn call_gen_prefix(offset: i32, len: i32) -> Vec<Base> {
 
     let mut result = Vec::new();
     result.extend_from_slice(&pattern::encode(&p));
+    result.extend_from_slice(&[I, I, C]);
     result.extend_from_slice(&template::encode(&t));
+    result.extend_from_slice(&[I, I, C]);
     return result;
 }
 
-crate::entry_point!("call_gen_prefix", call_gen_prefix_main);
-fn call_gen_prefix_main() {
+crate::entry_point!("call_gen_prefix", call_gen_prefix_main, "[offset] [len]",
+                    "Prints the prefix calling the gene at hex offset with hex len (default: 0x3c870e 0x00372b)");
+fn call_gen_prefix_main(args: &Args) -> Result<(), String> {
     // let offset = 0x000510; let len = 0x000018;
-    let offset = 0x3c870e; let len = 0x00372b;
+    let offset = hex(args.get("offset"), 0x3c870e)?;
+    let len = hex(args.get("len"), 0x00372b)?;
     let pref: String = call_gen_prefix(offset, len).iter().map(|b| b.to_char()).collect();
     println!("{:?}", pref);
+    Ok(())
+}
+
+fn hex(value: Option<&str>, default: i32) -> Result<i32, String> {
+    match value {
+        Some(value) => i32::from_str_radix(value.trim_start_matches("0x"), 16)
+            .map_err(|err| format!("Invalid hex number {:?}: {}", value, err)),
+        None => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpreter::Context;
+    use crate::interpreter::template::template;
+
+    #[test]
+    fn call_gen_prefix_test() {
+        use Base::*;
+        use PItem::*;
+        use TItem::*;
+        let prefix = call_gen_prefix(5, 2);
+        let mut context = Context::new(Dna::from_slice(&[prefix.clone(), vec![F, F]].concat()));
+        assert_eq!(pattern(&mut context), Ok(vec![Open, search("IFPICFPPCCC"), Open, search("IFPICFPPCCC"), Close, Close]));
+        // offset 5 and len 2 as nats, between the two groups
+        assert_eq!(template(&mut context), Ok(vec![Ref { n: 0, l: 0 }, TBase(C), TBase(I), TBase(C), TBase(P),
+                                                   TBase(I), TBase(C), TBase(P), Ref { n: 1, l: 0 }]));
+        assert_eq!(context.dna.len(), 2);
+        assert!(context.rna.is_empty());
+    }
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 
-fn catalog_page_prefix(n: u32) -> String {
+/// Prefix which renders catalog page `n` of the repair guide.
+pub fn catalog_page_prefix(n: u32) -> String {
     let s = format!("{:b}", n);
     let s2: String = s.chars().rev().map(|c| {
         match c {
@@ -12,8 +13,9 @@ fn catalog_page_prefix(n: u32) -> String {
     return format!("IIPIFFCPICFPPICIIC{}IICIPPP{}IIC", s1, s2);
 }
 
-crate::entry_point!("list_catalog_page_prefixes", list_catalog_page_prefixes);
-fn list_catalog_page_prefixes() {
+crate::entry_point!("list_catalog_page_prefixes", list_catalog_page_prefixes, "",
+                    "Prints the prefixes opening the known repair guide catalog pages");
+fn list_catalog_page_prefixes(_args: &crate::cli::Args) -> Result<(), String> {
     let ids = vec![(1337, "This catalog page"),
                    (1729, "Structure of the Funn Genome"),
                    (8, "More notes of Funn Genomics"),
@@ -34,4 +36,5 @@ fn list_catalog_page_prefixes() {
         println!("{}", catalog_page_prefix(id));
         println!("===================================================");
     }
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,260 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::ENTRY_POINTS;
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+// flags shared by many entry points, described once in the usage listing
+const COMMON_FLAGS: [(&str, &str); 3] = [
+    ("--data-dir <dir>", "directory with Endo's and tasks' DNA (default: data)"),
+    ("--out <dir>", "directory with results of the tasks (default: cache)"),
+    ("--limit-steps <n>", "stop the interpreter after n steps"),
+];
+
+pub struct EntryPoint {
+    pub name: &'static str,
+    /// Arguments in the form `<required> [optional] [--flag <value>] [--switch]`.
+    pub usage: &'static str,
+    pub help: &'static str,
+    pub run: fn(&Args) -> Result<(), String>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Param {
+    Positional { name: String, required: bool },
+    // `value` is the placeholder of the flag's value, None for switches
+    Flag { name: String, value: Option<String> },
+}
+
+fn parse_usage(usage: &str) -> Vec<Param> {
+    let mut params = Vec::new();
+    let mut tokens = usage.split_whitespace();
+    while let Some(token) = tokens.next() {
+        let mut token = token.to_string();
+        let required = !token.starts_with('[');
+        if !required {
+            while !token.ends_with(']') {
+                token.push(' ');
+                token.push_str(tokens.next().expect("Unclosed [ in usage"));
+            }
+            token = token[1..token.len() - 1].to_string();
+        }
+        match token.strip_prefix("--") {
+            Some(flag) => {
+                let mut parts = flag.split_whitespace();
+                params.push(Param::Flag {
+                    name: parts.next().unwrap().to_string(),
+                    value: parts.next().map(|value| value.to_string()),
+                });
+            }
+            None => {
+                let name = token.trim_start_matches('<').trim_end_matches('>').to_string();
+                params.push(Param::Positional { name, required });
+            }
+        }
+    }
+    params
+}
+
+/// Arguments of an entry point, checked against its usage.
+#[derive(Debug)]
+pub struct Args {
+    values: HashMap<String, String>,
+    switches: HashSet<String>,
+}
+
+impl Args {
+    pub fn from_usage(usage: &str, args: &[String]) -> Result<Self, String> {
+        let params = parse_usage(usage);
+        let positional: Vec<&Param> = params.iter()
+            .filter(|param| matches!(param, Param::Positional { .. }))
+            .collect();
+        let mut values = HashMap::new();
+        let mut switches = HashSet::new();
+        let mut next_positional = 0;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if let Some(flag) = arg.strip_prefix("--") {
+                let (name, inline) = match flag.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (flag, None),
+                };
+                let param = params.iter().find(|param| match param {
+                    Param::Flag { name: flag_name, .. } => flag_name == name,
+                    _ => false,
+                });
+                match param {
+                    Some(Param::Flag { value: Some(_), .. }) => {
+                        let value = inline.or_else(|| args.next().cloned())
+                            .ok_or_else(|| format!("--{} expects a value", name))?;
+                        values.insert(name.to_string(), value);
+                    }
+                    Some(Param::Flag { value: None, .. }) if inline.is_none() => {
+                        switches.insert(name.to_string());
+                    }
+                    Some(_) => return Err(format!("--{} takes no value", name)),
+                    None => return Err(format!("Unknown flag --{}", name)),
+                }
+            } else {
+                match positional.get(next_positional) {
+                    Some(Param::Positional { name, .. }) => {
+                        values.insert(name.clone(), arg.clone());
+                        next_positional += 1;
+                    }
+                    _ => return Err(format!("Unexpected argument {:?}", arg)),
+                }
+            }
+        }
+        for param in positional {
+            if let Param::Positional { name, required: true } = param {
+                if !values.contains_key(name) {
+                    return Err(format!("Missing <{}>", name));
+                }
+            }
+        }
+        Ok(Args { values, switches })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|value| value.as_str())
+    }
+
+    /// Value of a required positional argument, its presence is checked by `from_usage`.
+    pub fn arg(&self, name: &str) -> &str {
+        self.get(name).unwrap_or_else(|| panic!("<{}> is not a required argument", name))
+    }
+
+    pub fn parse_value<T>(&self, name: &str) -> Result<Option<T>, String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.get(name)
+            .map(|value| value.parse().map_err(|err| format!("Invalid {} {:?}: {}", name, value, err)))
+            .transpose()
+    }
+
+    pub fn switch(&self, name: &str) -> bool {
+        self.switches.contains(name)
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        PathBuf::from(self.get("data-dir").unwrap_or("data"))
+    }
+
+    pub fn out_dir(&self) -> PathBuf {
+        PathBuf::from(self.get("out").unwrap_or("cache"))
+    }
+}
+
+fn entry_point_usage(entry_point: &EntryPoint) -> String {
+    let line = format!("  {} {}", entry_point.name, entry_point.usage);
+    format!("{}\n      {}\n", line.trim_end(), entry_point.help)
+}
+
+pub fn usage() -> String {
+    let mut entry_points: Vec<&EntryPoint> = ENTRY_POINTS.iter().collect();
+    entry_points.sort_by_key(|entry_point| entry_point.name);
+    let mut usage = String::from("usage:\n  cargo run <entry point> [args...]\n  cargo run <entry point> --help\n\nentry points:\n");
+    for entry_point in entry_points {
+        usage.push_str(&entry_point_usage(entry_point));
+    }
+    usage.push_str("\ncommon flags:\n");
+    for (flag, help) in COMMON_FLAGS {
+        usage.push_str(&format!("  {:<18} {}\n", flag, help));
+    }
+    usage
+}
+
+/// Runs the entry point named by the first argument, returns the exit code.
+pub fn run(args: &[String]) -> i32 {
+    let (name, args) = match args.split_first() {
+        Some((name, args)) => (name, args),
+        None => {
+            eprintln!("Entry point not specified\n");
+            eprint!("{}", usage());
+            return EXIT_USAGE;
+        }
+    };
+    if name == "help" || name == "--help" || name == "-h" {
+        print!("{}", usage());
+        return EXIT_OK;
+    }
+    let entry_point = match ENTRY_POINTS.iter().find(|entry_point| entry_point.name == name) {
+        Some(entry_point) => entry_point,
+        None => {
+            if name == "gui" && cfg!(not(feature = "gui")) {
+                eprintln!("Built without the \"gui\" feature\n");
+            } else {
+                eprintln!("No entry point {:?}\n", name);
+            }
+            eprint!("{}", usage());
+            return EXIT_USAGE;
+        }
+    };
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", entry_point_usage(entry_point));
+        return EXIT_OK;
+    }
+    let args = match Args::from_usage(entry_point.usage, args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n", err);
+            eprint!("{}", entry_point_usage(entry_point));
+            return EXIT_USAGE;
+        }
+    };
+    match (entry_point.run)(&args) {
+        Ok(()) => EXIT_OK,
+        Err(err) => {
+            eprintln!("{}", err);
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(usage: &str, args: &[&str]) -> Result<Args, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Args::from_usage(usage, &args)
+    }
+
+    #[test]
+    fn parse_usage_test() {
+        assert_eq!(parse_usage("<task> [layer] [--out <dir>] [--bless]"), vec![
+            Param::Positional { name: "task".to_string(), required: true },
+            Param::Positional { name: "layer".to_string(), required: false },
+            Param::Flag { name: "out".to_string(), value: Some("<dir>".to_string()) },
+            Param::Flag { name: "bless".to_string(), value: None },
+        ]);
+    }
+
+    #[test]
+    fn args_test() {
+        let usage = "<task> [layer] [--limit-steps <n>] [--bless]";
+        let parsed = args(usage, &["--limit-steps", "10", "health_check", "--bless"]).unwrap();
+        assert_eq!(parsed.arg("task"), "health_check");
+        assert_eq!(parsed.get("layer"), None);
+        assert_eq!(parsed.parse_value::<usize>("limit-steps"), Ok(Some(10)));
+        assert!(parsed.switch("bless"));
+        assert_eq!(parsed.out_dir(), PathBuf::from("cache"));
+
+        let parsed = args(usage, &["task", "2", "--limit-steps=x"]).unwrap();
+        assert_eq!(parsed.get("layer"), Some("2"));
+        assert!(parsed.parse_value::<usize>("limit-steps").is_err());
+
+        assert!(args(usage, &[]).is_err());
+        assert!(args(usage, &["a", "b", "c"]).is_err());
+        assert!(args(usage, &["a", "--limit-steps"]).is_err());
+        assert!(args(usage, &["a", "--bless=yes"]).is_err());
+        assert!(args(usage, &["a", "--unknown"]).is_err());
+    }
+}
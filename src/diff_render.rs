@@ -0,0 +1,175 @@
+use image::{ImageBuffer, Rgba, RgbaImage};
+
+use crate::drawer::Drawer;
+use crate::image::DrawCommand;
+use crate::cli::Args;
+use crate::utils::read;
+
+#[derive(Debug)]
+pub struct Divergence {
+    // index of the command after which the bitmap stacks differ
+    pub command_idx: usize,
+    // index of the first differing layer, counted from the bottom of the stack
+    pub layer: usize,
+    pub left: RgbaImage,
+    pub right: RgbaImage,
+}
+
+fn mutates_bitmaps(command: &DrawCommand) -> bool {
+    matches!(command,
+             DrawCommand::Line |
+             DrawCommand::TryFill |
+             DrawCommand::AddBitmap |
+             DrawCommand::Compose |
+             DrawCommand::Clip)
+}
+
+fn layer_or_empty(drawer: &Drawer, layer: usize, like: &RgbaImage) -> RgbaImage {
+    drawer.bitmaps.get(layer).cloned().unwrap_or_else(|| {
+        ImageBuffer::new(like.width(), like.height())
+    })
+}
+
+// Stacks are known to be equal before the last command, and a command can only
+// change the top layer of the stack, so there is no need to compare the ones below.
+fn divergent_layer(a: &Drawer, b: &Drawer) -> Option<usize> {
+    let common = a.bitmaps.len().min(b.bitmaps.len());
+    let top = common - 1;
+    if a.bitmaps[top] != b.bitmaps[top] {
+        Some(top)
+    } else if a.bitmaps.len() != b.bitmaps.len() {
+        Some(common)
+    } else {
+        None
+    }
+}
+
+/// Replays both streams side by side and stops at the first command
+/// after which the bitmap stacks are not equal.
+/// A stream that is shorter than the other one is padded with no-ops.
+pub fn first_divergence(a: &[DrawCommand], b: &[DrawCommand]) -> Option<Divergence> {
+    let mut drawer_a = Drawer::new();
+    let mut drawer_b = Drawer::new();
+    for command_idx in 0..a.len().max(b.len()) {
+        let mut changed = false;
+        for (drawer, commands) in [(&mut drawer_a, a), (&mut drawer_b, b)] {
+            if let Some(command) = commands.get(command_idx) {
+                drawer.apply(*command);
+                changed |= mutates_bitmaps(command);
+            }
+        }
+        if !changed {
+            continue;
+        }
+        if let Some(layer) = divergent_layer(&drawer_a, &drawer_b) {
+            let (left, right) = if layer < drawer_a.bitmaps.len() {
+                let left = drawer_a.bitmaps[layer].clone();
+                let right = layer_or_empty(&drawer_b, layer, &left);
+                (left, right)
+            } else {
+                let right = drawer_b.bitmaps[layer].clone();
+                let left = layer_or_empty(&drawer_a, layer, &right);
+                (left, right)
+            };
+            return Some(Divergence { command_idx, layer, left, right });
+        }
+    }
+    None
+}
+
+pub fn side_by_side(left: &RgbaImage, right: &RgbaImage) -> RgbaImage {
+    let mut result = ImageBuffer::new(left.width() + right.width(), left.height().max(right.height()));
+    for (x, y, pixel) in left.enumerate_pixels() {
+        result.put_pixel(x, y, *pixel);
+    }
+    for (x, y, pixel) in right.enumerate_pixels() {
+        result.put_pixel(left.width() + x, y, *pixel);
+    }
+    result
+}
+
+/// Equal pixels become black, differing ones keep the xor of their colour channels.
+pub fn xor(left: &RgbaImage, right: &RgbaImage) -> RgbaImage {
+    ImageBuffer::from_fn(left.width(), left.height(), |x, y| {
+        let [r0, g0, b0, a0] = left.get_pixel(x, y).0;
+        let [r1, g1, b1, a1] = right.get_pixel(x, y).0;
+        if a0 != a1 && [r0, g0, b0] == [r1, g1, b1] {
+            // only transparency differs, make it visible anyway
+            Rgba([255, 255, 255, 255])
+        } else {
+            Rgba([r0 ^ r1, g0 ^ g1, b0 ^ b1, 255])
+        }
+    })
+}
+
+// one directory per pair, tasks in subdirectories are flattened
+fn pair_dir(task_a: &str, task_b: &str) -> String {
+    format!("{}-{}", task_a, task_b).replace('/', "_")
+}
+
+crate::entry_point!("diff-render", diff_render_main, "<task_a> <task_b> [--out <dir>]",
+                    "Finds the first command after which the tasks' bitmaps differ, saves them into diff/<task_a>-<task_b>/");
+fn diff_render_main(args: &Args) -> Result<(), String> {
+    let (task_a, task_b) = (args.arg("task_a"), args.arg("task_b"));
+    let commands_a: Vec<DrawCommand> = read(args.out_dir().join(task_a).join("commands.ron"))?;
+    let commands_b: Vec<DrawCommand> = read(args.out_dir().join(task_b).join("commands.ron"))?;
+    println!("{}: {} commands, {}: {} commands", task_a, commands_a.len(), task_b, commands_b.len());
+    match first_divergence(&commands_a, &commands_b) {
+        Some(divergence) => {
+            println!("Bitmaps diverge after command {} in layer {}", divergence.command_idx, divergence.layer);
+            println!("{}: {:?}", task_a, commands_a.get(divergence.command_idx));
+            println!("{}: {:?}", task_b, commands_b.get(divergence.command_idx));
+            let out_dir = args.out_dir().join("diff").join(pair_dir(task_a, task_b));
+            std::fs::create_dir_all(&out_dir).map_err(|err| err.to_string())?;
+            side_by_side(&divergence.left, &divergence.right).save(out_dir.join("side_by_side.png"))
+                .map_err(|err| err.to_string())?;
+            xor(&divergence.left, &divergence.right).save(out_dir.join("xor.png"))
+                .map_err(|err| err.to_string())?;
+            println!("Images written to {:?}", out_dir);
+        }
+        None => {
+            println!("Bitmaps are identical");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{Color, Rgb};
+
+    #[test]
+    fn pair_dir_test() {
+        assert_eq!(pair_dir("health_check", "repair_guide/initial_0"), "health_check-repair_guide_initial_0");
+        assert_ne!(pair_dir("a", "b"), pair_dir("b", "a"));
+    }
+
+    #[test]
+    fn identical_streams_test() {
+        let commands = vec![DrawCommand::AddColor(Color::Rgb(Rgb::Red)), DrawCommand::Move, DrawCommand::Line];
+        assert!(first_divergence(&commands, &commands).is_none());
+    }
+
+    #[test]
+    fn first_divergence_test() {
+        let a = vec![DrawCommand::Move, DrawCommand::Mark, DrawCommand::AddBitmap,
+                     DrawCommand::Move, DrawCommand::Line];
+        let b = vec![DrawCommand::Move, DrawCommand::Mark, DrawCommand::AddBitmap,
+                     DrawCommand::Move, DrawCommand::Move, DrawCommand::Line];
+        let divergence = first_divergence(&a, &b).unwrap();
+        assert_eq!(divergence.command_idx, 4);
+        assert_eq!(divergence.layer, 1);
+        let xor = xor(&divergence.left, &divergence.right);
+        assert_eq!(xor.get_pixel(2, 0), &Rgba([255, 255, 255, 255]));
+        assert_eq!(xor.get_pixel(5, 0), &Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn missing_layer_test() {
+        let a = vec![DrawCommand::AddBitmap];
+        let divergence = first_divergence(&a, &[]).unwrap();
+        assert_eq!(divergence.command_idx, 0);
+        assert_eq!(divergence.layer, 1);
+    }
+}
@@ -4,9 +4,9 @@ use std::convert::TryInto;
 use image::{ImageBuffer, Rgba, RgbaImage};
 use serde::{Deserialize, Serialize};
 
-use crate::image::{Color, DrawCommand, Pixel};
-use crate::utils::load;
-use std::path::PathBuf;
+use crate::cli::Args;
+use crate::image::{compress, expand, Color, CompactCommand, DrawCommand, Pixel};
+use crate::utils::{read, store_to, try_load};
 use std::rc::Rc;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
@@ -43,18 +43,39 @@ impl Direction {
     }
 }
 
-trait Bitmap {
-    fn set_pixel(&mut self, position: Position, pixel: Pixel);
-    fn draw_line(&mut self, p0: Position, p1: Position, pixel: Pixel);
-    fn fill(&mut self, p: Position, new: Pixel);
-    fn compose(&mut self, other: &RgbaImage);
-    fn clip(&mut self, other: &RgbaImage);
+// top + bottom * (255 - a0) / 255 as in the spec; every pixel is premultiplied
+// (a channel never exceeds its alpha), so this is at most a0 + 255 - a0
+fn compose_channel(top: u8, bottom: u8, a0: u8) -> u8 {
+    (top as u32 + bottom as u32 * (255 - a0 as u32) / 255) as u8
 }
 
-impl Bitmap for RgbaImage {
-    fn set_pixel(&mut self, position: Position, pixel: Pixel) {
-        self.put_pixel(position.x.try_into().unwrap(), position.y.try_into().unwrap(), pixel);
-    }
+fn compose_pixel(top: Pixel, bottom: Pixel) -> Pixel {
+    let [r0, g0, b0, a0] = top.0;
+    let [r1, g1, b1, a1] = bottom.0;
+    Rgba([
+        compose_channel(r0, r1, a0),
+        compose_channel(g0, g1, a0),
+        compose_channel(b0, b1, a0),
+        compose_channel(a0, a1, a0),
+    ])
+}
+
+#[allow(unused_variables)]
+fn clip_pixel(top: Pixel, bottom: Pixel) -> Pixel {
+    let [r0, g0, b0, a0] = top.0;
+    let [r1, g1, b1, a1] = bottom.0;
+    Rgba([
+        (r1 as u32 * a0 as u32 / 255) as u8,
+        (g1 as u32 * a0 as u32 / 255) as u8,
+        (b1 as u32 * a0 as u32 / 255) as u8,
+        (a1 as u32 * a0 as u32 / 255) as u8
+    ])
+}
+
+trait Bitmap {
+    fn size(&self) -> (i32, i32);
+    fn get(&self, position: Position) -> Pixel;
+    fn set_pixel(&mut self, position: Position, pixel: Pixel);
 
     fn draw_line(&mut self, p0: Position, p1: Position, pixel: Pixel) {
         let Position { x: x0, y: y0 } = p0;
@@ -63,12 +84,13 @@ impl Bitmap for RgbaImage {
         let delta_y = y1 - y0;
         let d = max(delta_x.abs(), delta_y.abs());
         let c = if delta_x * delta_y <= 0 { 1 } else { 0 };
-        let mut x = x0 * d + ((d - c) as f32 / 2f32).floor() as i32;
-        let mut y = y0 * d + ((d - c) as f32 / 2f32).floor() as i32;
+        // div_euclid is the floor of the spec, d - c is -1 for a single point
+        let mut x = x0 * d + (d - c).div_euclid(2);
+        let mut y = y0 * d + (d - c).div_euclid(2);
         for _ in 0..d {
             let position = Position {
-                x: (x as f32 / d as f32).floor() as i32,
-                y: (y as f32 / d as f32).floor() as i32,
+                x: x.div_euclid(d),
+                y: y.div_euclid(d),
             };
             self.set_pixel(position, pixel);
             x += delta_x;
@@ -78,10 +100,11 @@ impl Bitmap for RgbaImage {
     }
 
     fn fill(&mut self, p: Position, new: Pixel) {
-        let initial = *self.get_pixel(p.x as u32, p.y as u32);
+        let initial = self.get(p);
         if initial == new {
             return;
         }
+        let (width, height) = self.size();
         let mut stack = Vec::new();
         stack.push(p);
         loop {
@@ -90,8 +113,8 @@ impl Bitmap for RgbaImage {
                 for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
                     let x = current.x + dx;
                     let y = current.y + dy;
-                    if x >= 0 && x < self.width() as i32 && y >= 0 && y < self.height() as i32 &&
-                        *self.get_pixel(x as u32, y as u32) == initial {
+                    if x >= 0 && x < width && y >= 0 && y < height &&
+                        self.get(Position { x, y }) == initial {
                         stack.push(Position { x, y });
                     }
                 }
@@ -101,100 +124,208 @@ impl Bitmap for RgbaImage {
         }
     }
 
-    fn compose(&mut self, other: &RgbaImage) {
-        for y in 0..self.height() {
-            for x in 0..self.width() {
-                let [r0, g0, b0, a0] = other.get_pixel(x, y).0;
-                let [r1, g1, b1, a1] = self.get_pixel(x, y).0;
-                self.put_pixel(x, y, Rgba([
-                    r0 + ((r1 as u32) * ((255 - a0) as u32) / 255) as u8,
-                    g0 + ((g1 as u32) * ((255 - a0) as u32) / 255) as u8,
-                    b0 + ((b1 as u32) * ((255 - a0) as u32) / 255) as u8,
-                    a0 + ((a1 as u32) * ((255 - a0) as u32) / 255) as u8
-                ]));
+    // Only pixels whose value actually changes are written back.
+    fn blend(&mut self, other: &RgbaImage, f: fn(Pixel, Pixel) -> Pixel) {
+        let (width, height) = self.size();
+        for y in 0..height {
+            for x in 0..width {
+                let position = Position { x, y };
+                let old = self.get(position);
+                let new = f(*other.get_pixel(x as u32, y as u32), old);
+                if new != old {
+                    self.set_pixel(position, new);
+                }
             }
         }
     }
 
-    #[allow(unused_variables)]
+    fn compose(&mut self, other: &RgbaImage) {
+        self.blend(other, compose_pixel);
+    }
+
     fn clip(&mut self, other: &RgbaImage) {
-        for y in 0..self.height() {
-            for x in 0..self.width() {
-                let [r0, g0, b0, a0] = other.get_pixel(x, y).0;
-                let [r1, g1, b1, a1] = self.get_pixel(x, y).0;
-                self.put_pixel(x, y, Rgba([
-                    (r1 as u32 * a0 as u32 / 255) as u8,
-                    (g1 as u32 * a0 as u32 / 255) as u8,
-                    (b1 as u32 * a0 as u32 / 255) as u8,
-                    (a1 as u32 * a0 as u32 / 255) as u8
-                ]));
-            }
+        self.blend(other, clip_pixel);
+    }
+}
+
+impl Bitmap for RgbaImage {
+    fn size(&self) -> (i32, i32) {
+        (self.width() as i32, self.height() as i32)
+    }
+
+    fn get(&self, position: Position) -> Pixel {
+        *self.get_pixel(position.x as u32, position.y as u32)
+    }
+
+    fn set_pixel(&mut self, position: Position, pixel: Pixel) {
+        self.put_pixel(position.x.try_into().unwrap(), position.y.try_into().unwrap(), pixel);
+    }
+}
+
+/// Smallest rectangle containing every pixel written so far, bounds are inclusive.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+}
+
+impl Bounds {
+    fn include(bounds: Option<Bounds>, p: Position) -> Bounds {
+        match bounds {
+            Some(b) => Bounds {
+                min_x: b.min_x.min(p.x),
+                min_y: b.min_y.min(p.y),
+                max_x: b.max_x.max(p.x),
+                max_y: b.max_y.max(p.y),
+            },
+            None => Bounds { min_x: p.x, min_y: p.y, max_x: p.x, max_y: p.y },
         }
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+/// For every pixel of a layer, the index of the last command which wrote it.
+pub type Provenance = Vec<Option<u32>>;
+
+/// A bitmap which also stamps `command` into provenance of every pixel it writes.
+struct TrackedBitmap<'a> {
+    image: &'a mut RgbaImage,
+    provenance: Option<&'a mut Provenance>,
+    command: u32,
+    bounds: Option<&'a mut Option<Bounds>>,
+}
+
+impl Bitmap for TrackedBitmap<'_> {
+    fn size(&self) -> (i32, i32) {
+        self.image.size()
+    }
+
+    fn get(&self, position: Position) -> Pixel {
+        self.image.get(position)
+    }
+
+    fn set_pixel(&mut self, position: Position, pixel: Pixel) {
+        self.image.set_pixel(position, pixel);
+        if let Some(bounds) = &mut self.bounds {
+            **bounds = Some(Bounds::include(**bounds, position));
+        }
+        if let Some(provenance) = &mut self.provenance {
+            let idx = position.y as usize * self.image.width() as usize + position.x as usize;
+            provenance[idx] = Some(self.command);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Drawer {
-    bucket: Vec<Color>,
+    bucket: Bucket,
     position: Position,
     mark: Position,
     direction: Direction,
     // reversed stack
     pub bitmaps: Rc<Vec<RgbaImage>>,
+    // parallel to bitmaps, recorded only if the drawer was created with provenance
+    provenance: Option<Rc<Vec<Provenance>>>,
+    commands_applied: usize,
+    // recorded only if the drawer was created with bounds
+    records_bounds: bool,
+    drawn: Option<Bounds>,
     max_x: i32,
     max_y: i32,
 }
 
+// Drawers are equal if they draw the same from now on, how they got there doesn't matter
+impl PartialEq for Drawer {
+    fn eq(&self, other: &Self) -> bool {
+        self.bucket == other.bucket &&
+            self.position == other.position &&
+            self.mark == other.mark &&
+            self.direction == other.direction &&
+            self.bitmaps == other.bitmaps &&
+            self.max_x == other.max_x &&
+            self.max_y == other.max_y
+    }
+}
+
+impl Eq for Drawer {}
+
 fn empty_bitmap(max_x: i32, max_y: i32) -> RgbaImage {
     ImageBuffer::from_fn(max_x as u32, max_y as u32, |_x, _y| {
         Rgba([0, 0, 0, 0])
     })
 }
 
-fn current_pixel(bucket: &Vec<Color>) -> Pixel {
-    fn average(values: &Vec<u8>, default: u64) -> u64 {
-        if values.is_empty() {
-            return default;
-        } else {
-            let s: u64 = values.iter().fold(0u64, |acc, v| acc + *v as u64);
-            s / values.len() as u64
-        }
+fn empty_provenance(max_x: i32, max_y: i32) -> Provenance {
+    vec![None; (max_x * max_y) as usize]
+}
+
+#[derive(Debug, Clone, Default)]
+struct Bucket {
+    rgb_sum: [u64; 3],
+    rgb_count: u64,
+    alpha_sum: u64,
+    alpha_count: u64,
+    // memoised result of current_pixel, reset on every change
+    pixel: Option<Pixel>,
+}
+
+impl PartialEq for Bucket {
+    fn eq(&self, other: &Self) -> bool {
+        self.rgb_sum == other.rgb_sum &&
+            self.rgb_count == other.rgb_count &&
+            self.alpha_sum == other.alpha_sum &&
+            self.alpha_count == other.alpha_count
+    }
+}
+
+impl Eq for Bucket {}
+
+impl Bucket {
+    fn add(&mut self, color: Color) {
+        self.add_n(color, 1);
     }
-    let reds: Vec<_> = bucket.iter().filter_map(|color| {
+
+    fn add_n(&mut self, color: Color, n: u64) {
         match color {
             Color::Rgb(rgb) => {
-                Some(rgb.encode()[0])
+                for (sum, v) in self.rgb_sum.iter_mut().zip(rgb.encode()) {
+                    *sum += v as u64 * n;
+                }
+                self.rgb_count += n;
             }
-            Color::Alpha(_) => None
-        }
-    }).collect();
-    let grens: Vec<_> = bucket.iter().filter_map(|color| {
-        match color {
-            Color::Rgb(rgb) => {
-                Some(rgb.encode()[1])
+            Color::Alpha(alpha) => {
+                self.alpha_sum += alpha.encode() as u64 * n;
+                self.alpha_count += n;
             }
-            Color::Alpha(_) => None
         }
-    }).collect();
-    let blues: Vec<_> = bucket.iter().filter_map(|color| {
-        match color {
-            Color::Rgb(rgb) => {
-                Some(rgb.encode()[2])
-            }
-            Color::Alpha(_) => None
+        self.pixel = None;
+    }
+
+    fn clear(&mut self) {
+        *self = Bucket::default();
+    }
+
+    fn len(&self) -> u64 {
+        self.rgb_count + self.alpha_count
+    }
+
+    fn current_pixel(&mut self) -> Pixel {
+        if let Some(pixel) = self.pixel {
+            return pixel;
         }
-    }).collect();
-    let transparents: Vec<_> = bucket.iter().filter_map(|color| {
-        match color {
-            Color::Alpha(t) => Some(t.encode()),
-            _ => None
+        fn average(sum: u64, count: u64, default: u64) -> u64 {
+            sum.checked_div(count).unwrap_or(default)
         }
-    }).collect();
-    let transparency = average(&transparents, 255);
-    Rgba([(average(&reds, 0) * transparency / 255).try_into().unwrap(),
-          (average(&grens, 0) * transparency / 255).try_into().unwrap(),
-          (average(&blues, 0) * transparency / 255).try_into().unwrap(),
-          transparency.try_into().unwrap()])
+        let transparency = average(self.alpha_sum, self.alpha_count, 255);
+        let [r, g, b] = self.rgb_sum.map(|sum| average(sum, self.rgb_count, 0) * transparency / 255);
+        let pixel = Rgba([r.try_into().unwrap(),
+                          g.try_into().unwrap(),
+                          b.try_into().unwrap(),
+                          transparency.try_into().unwrap()]);
+        self.pixel = Some(pixel);
+        pixel
+    }
 }
 
 impl Drawer {
@@ -202,55 +333,139 @@ impl Drawer {
         let max_x = 600;
         let max_y = 600;
         Drawer {
-            bucket: Vec::new(),
+            bucket: Bucket::default(),
             position: Position { x: 0, y: 0},
             mark: Position { x: 0, y: 0},
             direction: Direction::East,
             bitmaps: Rc::new(vec![empty_bitmap(max_x, max_y)]),
+            provenance: None,
+            commands_applied: 0,
+            records_bounds: false,
+            drawn: None,
             max_x,
             max_y,
         }
     }
 
-    fn current_pixel(&self) -> Pixel {
-        current_pixel(&self.bucket)
+    pub fn with_provenance() -> Self {
+        let mut drawer = Drawer::new();
+        drawer.provenance = Some(Rc::new(vec![empty_provenance(drawer.max_x, drawer.max_y)]));
+        drawer
+    }
+
+    /// A drawer which also keeps the bounds of everything drawn, see `drawn_bounds`.
+    pub fn with_bounds() -> Self {
+        let mut drawer = Drawer::new();
+        drawer.records_bounds = true;
+        drawer
+    }
+
+    pub fn records_provenance(&self) -> bool {
+        self.provenance.is_some()
+    }
+
+    /// Memory taken by the layers and their provenance, layers shared with clones count in full.
+    pub fn size_in_bytes(&self) -> usize {
+        let pixels = (self.max_x * self.max_y) as usize;
+        let provenance = self.provenance.as_ref().map_or(0, |provenance| provenance.len());
+        self.bitmaps.len() * pixels * 4 + provenance * pixels * std::mem::size_of::<Option<u32>>()
+    }
+
+    /// Index of the last command which wrote pixel (x, y) of the given layer,
+    /// None if the pixel was never painted or provenance is not recorded.
+    pub fn who_painted(&self, layer: usize, x: u32, y: u32) -> Option<usize> {
+        if x >= self.max_x as u32 || y >= self.max_y as u32 {
+            return None;
+        }
+        let provenance = self.provenance.as_ref()?.get(layer)?;
+        provenance[y as usize * self.max_x as usize + x as usize].map(|idx| idx as usize)
+    }
+
+    fn top_bitmap(&mut self) -> TrackedBitmap<'_> {
+        TrackedBitmap {
+            image: Rc::make_mut(&mut self.bitmaps).last_mut().unwrap(),
+            provenance: self.provenance.as_mut().map(|p| Rc::make_mut(p).last_mut().unwrap()),
+            command: self.commands_applied as u32,
+            bounds: if self.records_bounds { Some(&mut self.drawn) } else { None },
+        }
+    }
+
+    // Removes the top layer, returns the image to be composed into the new top.
+    fn pop_bitmap(&mut self) -> RgbaImage {
+        if let Some(provenance) = &mut self.provenance {
+            Rc::make_mut(provenance).pop();
+        }
+        Rc::make_mut(&mut self.bitmaps).pop().unwrap()
+    }
+
+    fn current_pixel(&mut self) -> Pixel {
+        self.bucket.current_pixel()
+    }
+
+    pub fn position(&self) -> (i32, i32) {
+        (self.position.x, self.position.y)
     }
 
-    pub fn apply(&mut self, command: DrawCommand) {
+    pub fn mark(&self) -> (i32, i32) {
+        (self.mark.x, self.mark.y)
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn bucket_len(&self) -> usize {
+        self.bucket.len() as usize
+    }
+
+    /// None if nothing was drawn yet or bounds are not recorded.
+    pub fn drawn_bounds(&self) -> Option<Bounds> {
+        self.drawn
+    }
+
+    fn move_by(&mut self, n: u32) {
+        let n = n as i64;
+        let Position { x, y } = self.position;
+        let (x, y) = match self.direction {
+            Direction::North => (x as i64, y as i64 - n),
+            Direction::East => (x as i64 + n, y as i64),
+            Direction::South => (x as i64, y as i64 + n),
+            Direction::West => (x as i64 - n, y as i64),
+        };
+        self.position = Position {
+            x: x.rem_euclid(self.max_x as i64) as i32,
+            y: y.rem_euclid(self.max_y as i64) as i32,
+        };
+    }
+
+    /// Accepts both plain and compact commands, a compact command counts
+    /// as all the plain commands it stands for.
+    pub fn apply<C: Into<CompactCommand>>(&mut self, command: C) {
+        match command.into() {
+            CompactCommand::Command(command) => {
+                self.apply_command(command);
+            }
+            CompactCommand::MoveN(n) => {
+                self.move_by(n);
+                self.commands_applied += n as usize;
+            }
+            CompactCommand::AddColorN(color, n) => {
+                self.bucket.add_n(color, n as u64);
+                self.commands_applied += n as usize;
+            }
+        }
+    }
+
+    fn apply_command(&mut self, command: DrawCommand) {
         match command {
             DrawCommand::AddColor(color) => {
-                self.bucket.push(color);
+                self.bucket.add(color);
             }
             DrawCommand::ClearBucket => {
                 self.bucket.clear()
             }
             DrawCommand::Move => {
-                match self.direction {
-                    Direction::North => {
-                        self.position = Position {
-                            x: self.position.x,
-                            y: (self.position.y - 1).rem_euclid(self.max_y)
-                        };
-                    }
-                    Direction::East => {
-                        self.position = Position {
-                            x: (self.position.x + 1).rem_euclid(self.max_x),
-                            y: self.position.y
-                        };
-                    }
-                    Direction::South => {
-                        self.position = Position {
-                            x: self.position.x,
-                            y: (self.position.y + 1).rem_euclid(self.max_y),
-                        };
-                    }
-                    Direction::West => {
-                        self.position = Position {
-                            x: (self.position.x - 1).rem_euclid(self.max_x),
-                            y: self.position.y
-                        };
-                    }
-                }
+                self.move_by(1);
             }
             DrawCommand::TurnCC => {
                 self.direction = self.direction.turn_cc();
@@ -263,49 +478,51 @@ impl Drawer {
             }
             DrawCommand::Line => {
                 let current_pixel = self.current_pixel();
-                Rc::make_mut(&mut self.bitmaps)
-                    .last_mut().unwrap()
-                    .draw_line(self.position, self.mark, current_pixel);
+                let (position, mark) = (self.position, self.mark);
+                self.top_bitmap().draw_line(position, mark, current_pixel);
             }
             DrawCommand::TryFill => {
                 let current_pixel = self.current_pixel();
-                Rc::make_mut(&mut self.bitmaps)
-                    .last_mut().unwrap()
-                    .fill(self.position, current_pixel);
+                let position = self.position;
+                self.top_bitmap().fill(position, current_pixel);
             }
             DrawCommand::AddBitmap => {
                 if self.bitmaps.len() < 10 {
                     Rc::make_mut(&mut self.bitmaps).push(empty_bitmap(self.max_x, self.max_y));
+                    let (max_x, max_y) = (self.max_x, self.max_y);
+                    if let Some(provenance) = &mut self.provenance {
+                        Rc::make_mut(provenance).push(empty_provenance(max_x, max_y));
+                    }
                 }
             }
             DrawCommand::Compose => {
                 if self.bitmaps.len() >= 2 {
-                    let bitmaps = Rc::make_mut(&mut self.bitmaps);
-                    let top = bitmaps.pop().unwrap();
-                    bitmaps.last_mut().unwrap().compose(&top);
+                    let top = self.pop_bitmap();
+                    self.top_bitmap().compose(&top);
                 }
             }
             DrawCommand::Clip => {
                 if self.bitmaps.len() >= 2 {
-                    let bitmaps = Rc::make_mut(&mut self.bitmaps);
-                    let top = bitmaps.pop().unwrap();
-                    bitmaps.last_mut().unwrap().clip(&top);
+                    let top = self.pop_bitmap();
+                    self.top_bitmap().clip(&top);
                 }
             }
         }
+        self.commands_applied += 1;
     }
 
-    pub fn apply_all(&mut self, commands: &[DrawCommand]) {
+    pub fn apply_all<C: Into<CompactCommand> + Copy>(&mut self, commands: &[C]) {
         for command in commands {
             self.apply(*command)
         }
     }
 }
 
-crate::entry_point!("drawer", drawer_main);
-fn drawer_main() {
-    let folder = std::env::args().nth(2).expect("Not enough arguments");
-    let commands: Vec<DrawCommand> = load(["cache", &folder, "commands.ron"].iter().collect::<PathBuf>());
+crate::entry_point!("drawer", drawer_main, "<task> [--out <dir>]",
+                    "Draws the task's commands, saves every layer as result<n>.png");
+fn drawer_main(args: &Args) -> Result<(), String> {
+    let out_dir = args.out_dir().join(args.arg("task"));
+    let commands: Vec<DrawCommand> = read(out_dir.join("commands.ron"))?;
     let mut drawer = Drawer::new();
     // let images_dir = ["cache", &folder, "images"].iter().collect::<PathBuf>();
     // if images_dir.exists() {
@@ -320,10 +537,49 @@ fn drawer_main() {
     }
     // drawer.bitmaps.last_mut().unwrap().fill(Position {x : 0, y: 0},  Rgba([0, 0, 0, 255]));
     for (idx, bitmap) in drawer.bitmaps.iter().enumerate() {
-        bitmap.save(["cache", &folder, &format!("result{}.png", idx)].iter().collect::<PathBuf>()).unwrap();
+        bitmap.save(out_dir.join(format!("result{}.png", idx))).map_err(|err| err.to_string())?;
     }
+    Ok(())
 }
 
+crate::entry_point!("compress", compress_main, _EP_COMPRESS, "<task> [--out <dir>]",
+                    "Stores the task's commands in the compact form as compact.ron");
+fn compress_main(args: &Args) -> Result<(), String> {
+    let out_dir = args.out_dir().join(args.arg("task"));
+    let commands: Vec<DrawCommand> = read(out_dir.join("commands.ron"))?;
+    let compact = compress(&commands);
+    assert_eq!(expand(&compact), commands);
+    println!("Compressed {} commands into {}", commands.len(), compact.len());
+    store_to(&compact, out_dir.join("compact.ron"));
+    Ok(())
+}
+
+crate::entry_point!("who_painted", who_painted_main, _EP_WHO_PAINTED, "<task> <x> <y> [layer] [--out <dir>]",
+                    "Finds the command and the interpreter step which last painted a pixel, on the top layer by default");
+fn who_painted_main(args: &Args) -> Result<(), String> {
+    let out_dir = args.out_dir().join(args.arg("task"));
+    let x: u32 = args.parse_value("x")?.unwrap();
+    let y: u32 = args.parse_value("y")?.unwrap();
+    let commands: Vec<DrawCommand> = read(out_dir.join("commands.ron"))?;
+    let steps: Option<Vec<usize>> = try_load(out_dir.join("steps.ron"));
+    let mut drawer = Drawer::with_provenance();
+    drawer.apply_all(&commands);
+    let layer = args.parse_value("layer")?.unwrap_or(drawer.bitmaps.len() - 1);
+    if layer >= drawer.bitmaps.len() || x >= drawer.max_x as u32 || y >= drawer.max_y as u32 {
+        return Err(format!("({}, {}) of layer {} is out of the bitmaps", x, y, layer));
+    }
+    match drawer.who_painted(layer, x, y) {
+        Some(idx) => {
+            let step = steps.as_ref().and_then(|steps| steps.get(idx));
+            println!("({}, {}) of layer {} painted by command {}: {:?}, interpreter step: {:?}",
+                     x, y, layer, idx, commands[idx], step);
+        }
+        None => {
+            println!("({}, {}) of layer {} was never painted", x, y, layer);
+        }
+    }
+    Ok(())
+}
 
 #[cfg(test)]
 mod tests {
@@ -378,6 +634,48 @@ mod tests {
         });
     }
 
+    fn current_pixel(colors: &[Color]) -> Pixel {
+        let mut bucket = Bucket::default();
+        for color in colors {
+            bucket.add(*color);
+        }
+        bucket.current_pixel()
+    }
+
+    #[test]
+    fn provenance_test() {
+        let mut drawer = Drawer::with_provenance();
+        drawer.apply_all(&[DrawCommand::Move, DrawCommand::Move, DrawCommand::Line,
+                           DrawCommand::AddBitmap, DrawCommand::TryFill,
+                           DrawCommand::AddColor(Color::Alpha(Alpha::Transparent)),
+                           DrawCommand::Mark, DrawCommand::TurnC, DrawCommand::Move, DrawCommand::Line,
+                           DrawCommand::Compose]);
+        // compose keeps provenance of pixels it doesn't change
+        assert_eq!(drawer.who_painted(0, 1, 0), Some(2));
+        assert_eq!(drawer.who_painted(0, 2, 0), Some(2));
+        assert_eq!(drawer.who_painted(0, 3, 0), Some(10));
+        assert_eq!(drawer.who_painted(0, 2, 1), None);
+        assert_eq!(drawer.who_painted(1, 3, 0), None);
+        assert!(Drawer::new().who_painted(0, 0, 0).is_none());
+    }
+
+    #[test]
+    fn compact_apply_test() {
+        let commands: Vec<_> = std::iter::repeat(DrawCommand::Move).take(1000)
+            .chain([DrawCommand::AddColor(Color::Rgb(Rgb::Red)); 5])
+            .chain([DrawCommand::TurnCC, DrawCommand::Move, DrawCommand::Move, DrawCommand::Line])
+            .collect();
+        let mut plain = Drawer::with_provenance();
+        plain.apply_all(&commands);
+        let mut compact = Drawer::with_provenance();
+        compact.apply_all(&compress(&commands));
+        assert_eq!(plain, compact);
+        assert_eq!(plain.provenance, compact.provenance);
+        assert_eq!(compact.position, Position { x: 400, y: 598 });
+        assert_eq!(compact.who_painted(0, 400, 598), Some(1008));
+        assert_eq!(compact.who_painted(0, 0, 0), Some(1008));
+    }
+
     #[test]
     fn current_pixel_test() {
         assert_eq!(current_pixel(&vec![]), Rgba([0, 0, 0, 255]));
@@ -401,4 +699,146 @@ mod tests {
                                 std::iter::repeat(t).take(1)))))).collect();
         assert_eq!(current_pixel(&bucket), Rgba([143, 25, 125, 191]));
     }
+
+    fn pixel(drawer: &Drawer, layer: usize, x: u32, y: u32) -> Pixel {
+        *drawer.bitmaps[layer].get_pixel(x, y)
+    }
+
+    fn drawn(commands: &[DrawCommand]) -> Drawer {
+        let mut drawer = Drawer::new();
+        drawer.apply_all(commands);
+        drawer
+    }
+
+    #[test]
+    fn blend_pixel_test() {
+        let white = Rgba([255, 255, 255, 255]);
+        assert_eq!(compose_pixel(Rgba([10, 20, 30, 40]), white), Rgba([225, 235, 245, 255]));
+        assert_eq!(compose_pixel(Rgba([0, 0, 0, 0]), Rgba([1, 2, 3, 4])), Rgba([1, 2, 3, 4]));
+        // the largest premultiplied channels add up to exactly 255
+        for a in 0..=255 {
+            assert_eq!(compose_pixel(Rgba([a, a, a, a]), white), white);
+        }
+        assert_eq!(clip_pixel(Rgba([0, 0, 0, 128]), Rgba([255, 100, 0, 255])), Rgba([128, 50, 0, 128]));
+        assert_eq!(clip_pixel(Rgba([9, 9, 9, 0]), white), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn spec_line_test() {
+        let line = |p0: (i32, i32), p1: (i32, i32)| {
+            let mut bitmap = empty_bitmap(8, 8);
+            let pixel = Rgba([1, 1, 1, 1]);
+            bitmap.draw_line(Position { x: p0.0, y: p0.1 }, Position { x: p1.0, y: p1.1 }, pixel);
+            let mut painted = Vec::new();
+            for (x, y, p) in bitmap.enumerate_pixels() {
+                if *p == pixel {
+                    painted.push((x, y));
+                }
+            }
+            painted.sort();
+            painted
+        };
+        assert_eq!(line((0, 0), (4, 2)), vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]);
+        assert_eq!(line((4, 2), (0, 0)), vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]);
+        assert_eq!(line((0, 2), (4, 0)), vec![(0, 2), (1, 1), (2, 1), (3, 0), (4, 0)]);
+        assert_eq!(line((3, 0), (0, 0)), vec![(0, 0), (1, 0), (2, 0), (3, 0)]);
+        assert_eq!(line((5, 5), (5, 5)), vec![(5, 5)]);
+    }
+
+    #[test]
+    fn move_and_turn_test() {
+        use DrawCommand::*;
+        // starts facing east, moves wrap around the 600x600 canvas
+        assert_eq!(drawn(&[Move]).position(), (1, 0));
+        assert_eq!(drawn(&[TurnC, Move]).position(), (0, 1));
+        assert_eq!(drawn(&[TurnC, TurnC, Move]).position(), (599, 0));
+        assert_eq!(drawn(&[TurnCC, Move]).position(), (0, 599));
+        assert_eq!(drawn(&[TurnCC, TurnCC, TurnCC, Move]).position(), (0, 1));
+        assert_eq!(drawn(&[TurnC, TurnC, TurnC, TurnC]).direction(), Direction::East);
+        let mut drawer = drawn(&[TurnCC, Move, Mark, TurnCC, Move]);
+        assert_eq!(drawer.position(), (599, 599));
+        assert_eq!(drawer.mark(), (0, 599));
+        drawer.apply(CompactCommand::MoveN(601));
+        assert_eq!(drawer.position(), (598, 599));
+        // east past 599 and south past 599 come back to 0
+        let mut east = Drawer::new();
+        east.apply(CompactCommand::MoveN(599));
+        assert_eq!(east.position(), (599, 0));
+        east.apply(Move);
+        assert_eq!(east.position(), (0, 0));
+        let mut south = drawn(&[TurnC]);
+        south.apply(CompactCommand::MoveN(599));
+        assert_eq!(south.position(), (0, 599));
+        south.apply(Move);
+        assert_eq!(south.position(), (0, 0));
+    }
+
+    #[test]
+    fn bucket_and_line_test() {
+        use DrawCommand::*;
+        let drawer = drawn(&[AddColor(Color::Rgb(Rgb::Red)), Mark, Move, Move, Move, Line,
+                             ClearBucket, TurnC, Move, Mark, Line]);
+        for x in 0..4 {
+            assert_eq!(pixel(&drawer, 0, x, 0), Rgba([255, 0, 0, 255]));
+        }
+        assert_eq!(pixel(&drawer, 0, 4, 0), Rgba([0, 0, 0, 0]));
+        // an empty bucket is opaque black
+        assert_eq!(pixel(&drawer, 0, 3, 1), Rgba([0, 0, 0, 255]));
+        assert_eq!(drawer.bucket_len(), 0);
+    }
+
+    #[test]
+    fn try_fill_test() {
+        use DrawCommand::*;
+        let drawer = drawn(&[Move, Move, TurnC, Mark, Move, Move, Line,
+                             AddColor(Color::Rgb(Rgb::Blue)), TurnC, Move, TryFill]);
+        assert_eq!(pixel(&drawer, 0, 2, 0), Rgba([0, 0, 0, 255]));
+        assert_eq!(pixel(&drawer, 0, 1, 2), Rgba([0, 0, 255, 255]));
+        assert_eq!(pixel(&drawer, 0, 599, 599), Rgba([0, 0, 255, 255]));
+        // filling with the colour already there changes nothing
+        let mut again = drawer.clone();
+        again.apply(TryFill);
+        assert_eq!(again.bitmaps, drawer.bitmaps);
+    }
+
+    #[test]
+    fn add_bitmap_limit_test() {
+        let drawer = drawn(&[DrawCommand::AddBitmap; 12]);
+        assert_eq!(drawer.bitmaps.len(), 10);
+    }
+
+    // red opaque layer under a layer with one half transparent green pixel at (0, 0)
+    fn two_layers() -> Drawer {
+        use DrawCommand::*;
+        drawn(&[AddColor(Color::Rgb(Rgb::Red)), TryFill, AddBitmap, ClearBucket,
+                AddColor(Color::Rgb(Rgb::Green)), AddColor(Color::Alpha(Alpha::Transparent)),
+                AddColor(Color::Alpha(Alpha::Opaque)), Line])
+    }
+
+    #[test]
+    fn compose_and_clip_test() {
+        let mut composed = two_layers();
+        assert_eq!(pixel(&composed, 1, 0, 0), Rgba([0, 127, 0, 127]));
+        composed.apply(DrawCommand::Compose);
+        assert_eq!(composed.bitmaps.len(), 1);
+        assert_eq!(pixel(&composed, 0, 0, 0), Rgba([128, 127, 0, 255]));
+        assert_eq!(pixel(&composed, 0, 1, 0), Rgba([255, 0, 0, 255]));
+
+        let mut clipped = two_layers();
+        clipped.apply(DrawCommand::Clip);
+        assert_eq!(clipped.bitmaps.len(), 1);
+        assert_eq!(pixel(&clipped, 0, 0, 0), Rgba([127, 0, 0, 127]));
+        assert_eq!(pixel(&clipped, 0, 1, 0), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn single_layer_compose_and_clip_test() {
+        use DrawCommand::*;
+        let drawer = drawn(&[Move, Line]);
+        for command in [Compose, Clip] {
+            let mut after = drawer.clone();
+            after.apply(command);
+            assert_eq!(after.bitmaps, drawer.bitmaps);
+        }
+    }
 }
\ No newline at end of file
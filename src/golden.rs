@@ -0,0 +1,138 @@
+//! Golden hashes of the command stream and of the final image of every repair guide page,
+//! checked in as `<data_dir>/repair_guide/<page>/golden.ron`.
+//! After an intentional change of the output, update them with `cargo run golden --bless`.
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::Args;
+use crate::drawer::Drawer;
+use crate::interpreter::dna::Dna;
+use crate::interpreter::runner::{dna_for_task, produce_draw_commands};
+use crate::utils::{read, store_to};
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Golden {
+    pub commands: usize,
+    pub commands_hash: String,
+    pub image_hash: String,
+}
+
+// FNV-1a, unlike DefaultHasher it is the same on every platform and Rust version
+fn hash<I: IntoIterator<Item = u8>>(bytes: I) -> String {
+    let hash = bytes.into_iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Runs the interpreter and the drawer, hashes commands as stored in commands.ron
+/// and the top bitmap.
+pub fn golden_for(dna: Dna) -> Golden {
+    let commands = produce_draw_commands(dna);
+    let mut drawer = Drawer::new();
+    drawer.apply_all(&commands);
+    Golden {
+        commands: commands.len(),
+        commands_hash: hash(ron::to_string(&commands).unwrap().into_bytes()),
+        image_hash: hash(drawer.bitmaps.last().unwrap().as_raw().iter().cloned()),
+    }
+}
+
+fn pages(data_dir: &Path) -> Result<Vec<String>, String> {
+    let dir = data_dir.join("repair_guide");
+    let entries = std::fs::read_dir(&dir)
+        .map_err(|err| format!("Failed to list {}: {}", dir.display(), err))?;
+    let mut pages: Vec<String> = entries
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.join("dna").exists())
+        .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    pages.sort();
+    Ok(pages)
+}
+
+/// Compares every page which has a golden, or rewrites the goldens of all pages with `bless`.
+/// Fails when there was nothing to compare, so a missing golden doesn't pass silently.
+pub fn check_goldens(data_dir: &Path, page: Option<&str>, bless: bool) -> Result<(), String> {
+    let pages = match page {
+        Some(page) => vec![page.to_string()],
+        None => pages(data_dir)?,
+    };
+    let mut checked = 0;
+    let mut diverged = Vec::new();
+    for page in pages {
+        let task: PathBuf = ["repair_guide", &page].iter().collect();
+        let golden_path = data_dir.join(&task).join("golden.ron");
+        if !bless && !golden_path.exists() {
+            println!("{}: no golden, skipped", page);
+            continue;
+        }
+        let actual = golden_for(dna_for_task(data_dir, task.to_string_lossy())?);
+        checked += 1;
+        if bless {
+            println!("{}: blessed {:?}", page, actual);
+            store_to(&actual, &golden_path);
+            continue;
+        }
+        let expected: Golden = read(&golden_path)?;
+        if actual == expected {
+            println!("{}: ok", page);
+        } else {
+            println!("{}: expected {:?}, got {:?}", page, expected, actual);
+            diverged.push(page);
+        }
+    }
+    if checked == 0 {
+        Err(format!("No goldens in {}, create them with `golden --bless`", data_dir.join("repair_guide").display()))
+    } else if diverged.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Diverged from the goldens: {}", diverged.join(", ")))
+    }
+}
+
+crate::entry_point!("golden", golden_main, "[page] [--data-dir <dir>] [--bless]",
+                    "Checks repair guide pages (all by default) against their goldens, --bless updates them");
+fn golden_main(args: &Args) -> Result<(), String> {
+    check_goldens(&args.data_dir(), args.get("page"), args.switch("bless"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_test() {
+        assert_eq!(hash(Vec::new()), "cbf29ce484222325");
+        assert_eq!(hash(b"a".to_vec()), "af63dc4c8601ec8c");
+    }
+
+    // III emits the next seven bases as rna, then an empty pattern and template end the dna
+    fn drawing_dna() -> Dna {
+        let rna = ["PIPIIIP", "PIIIIIP", "PIIIIIP", "PIIIIIP", "PCCIFFP", "PFFFFFP", "PIIIIIP",
+                   "PIIIIIP", "PFFICCP", "PCCPFFP", "PIPIIPP", "PIIPIIP", "PFFPCCP"];
+        let dna: String = rna.iter().map(|rna| format!("III{}", rna)).collect();
+        Dna::from_string(&format!("{}IICIIC", dna)).unwrap()
+    }
+
+    #[test]
+    fn drawing_golden_test() {
+        let golden = golden_for(drawing_dna());
+        assert_eq!(golden, Golden {
+            commands: 13,
+            commands_hash: "41bbf8e54042b9d8".to_string(),
+            image_hash: "7ca54dedad7e8aa5".to_string(),
+        });
+        assert_ne!(golden.image_hash, golden_for(Dna::empty()).image_hash);
+    }
+
+    /// Checks every repair guide page against its golden, needs Endo's DNA in data/endo/dna.
+    /// Slow in debug builds, `cargo test --release golden_test` is faster.
+    #[test]
+    fn golden_test() {
+        assert!(Path::new("data/endo/dna").exists(),
+                "golden_test needs Endo's DNA in data/endo/dna, the goldens can't be checked without it");
+        check_goldens(Path::new("data"), None, false).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+use eframe::egui::{pos2, vec2, Color32, Pos2, Rect, Sense, Shape, Stroke, TextureHandle, Ui, Vec2};
+use image::RgbaImage;
+
+use crate::drawer::{Direction, Drawer};
+
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 64f32;
+// pixel grid is drawn starting from this zoom
+const GRID_ZOOM: f32 = 8f32;
+
+/// Zoomable (mouse wheel) and pannable (drag) view of one layer.
+pub struct Canvas {
+    zoom: f32,
+    // screen offset of the image origin from the top left corner of the canvas
+    pan: Vec2,
+}
+
+pub struct CanvasResponse {
+    pub hovered: Option<(u32, u32)>,
+    pub clicked: Option<(u32, u32)>,
+}
+
+impl Canvas {
+    pub fn new() -> Self {
+        Canvas { zoom: 1f32, pan: Vec2::ZERO }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Canvas::new();
+    }
+
+    fn pixel_at(&self, image_rect: Rect, image: &RgbaImage, pos: Pos2) -> Option<(u32, u32)> {
+        let p = (pos - image_rect.min) / self.zoom;
+        if p.x >= 0f32 && p.y >= 0f32 && (p.x as u32) < image.width() && (p.y as u32) < image.height() {
+            Some((p.x as u32, p.y as u32))
+        } else {
+            None
+        }
+    }
+
+    /// `overlay` is drawn over the image with the given tint.
+    pub fn show(&mut self, ui: &mut Ui, size: Vec2, texture: &TextureHandle, image: &RgbaImage, drawer: &Drawer,
+                overlay: Option<(&TextureHandle, Color32)>) -> CanvasResponse {
+        let (response, painter) = ui.allocate_painter(size, Sense::click_and_drag());
+        let rect = response.rect;
+        if response.dragged() {
+            self.pan += response.drag_delta();
+        }
+        if let Some(cursor) = response.hover_pos() {
+            let scroll = ui.input().scroll_delta.y;
+            if scroll != 0f32 {
+                let zoom = (self.zoom * (scroll / 200f32).exp()).clamp(MIN_ZOOM, MAX_ZOOM);
+                // keep the pixel under the cursor in place
+                let anchor = cursor - rect.min;
+                self.pan = anchor - (anchor - self.pan) * (zoom / self.zoom);
+                self.zoom = zoom;
+            }
+        }
+
+        painter.rect_filled(rect, 0f32, Color32::from_gray(20));
+        let image_size = vec2(image.width() as f32, image.height() as f32);
+        let image_rect = Rect::from_min_size(rect.min + self.pan, image_size * self.zoom);
+        let uv = Rect::from_min_max(pos2(0f32, 0f32), pos2(1f32, 1f32));
+        painter.add(Shape::image(texture.id(), image_rect, uv, Color32::WHITE));
+        if let Some((overlay, tint)) = overlay {
+            painter.add(Shape::image(overlay.id(), image_rect, uv, tint));
+        }
+
+        let to_screen = |(x, y): (i32, i32)| image_rect.min + vec2(x as f32, y as f32) * self.zoom;
+        if self.zoom >= GRID_ZOOM {
+            let visible = rect.intersect(image_rect);
+            let stroke = Stroke::new(1f32, Color32::from_gray(60));
+            let first_x = ((visible.min.x - image_rect.min.x) / self.zoom).floor() as i32;
+            let last_x = ((visible.max.x - image_rect.min.x) / self.zoom).ceil() as i32;
+            let first_y = ((visible.min.y - image_rect.min.y) / self.zoom).floor() as i32;
+            let last_y = ((visible.max.y - image_rect.min.y) / self.zoom).ceil() as i32;
+            for x in first_x..=last_x {
+                let sx = to_screen((x, 0)).x;
+                painter.line_segment([pos2(sx, visible.min.y), pos2(sx, visible.max.y)], stroke);
+            }
+            for y in first_y..=last_y {
+                let sy = to_screen((0, y)).y;
+                painter.line_segment([pos2(visible.min.x, sy), pos2(visible.max.x, sy)], stroke);
+            }
+        }
+
+        // turtle overlays, outlined so that they stay visible when zoomed out
+        let pixel_rect = |p: (i32, i32)| {
+            Rect::from_center_size(to_screen(p) + Vec2::splat(self.zoom / 2f32), Vec2::splat(self.zoom.max(5f32)))
+        };
+        painter.rect_stroke(pixel_rect(drawer.mark()), 0f32, Stroke::new(2f32, Color32::YELLOW));
+        let position = pixel_rect(drawer.position());
+        painter.rect_stroke(position, 0f32, Stroke::new(2f32, Color32::RED));
+        let direction = match drawer.direction() {
+            Direction::North => vec2(0f32, -1f32),
+            Direction::East => vec2(1f32, 0f32),
+            Direction::South => vec2(0f32, 1f32),
+            Direction::West => vec2(-1f32, 0f32),
+        };
+        painter.arrow(position.center(), direction * (self.zoom * 3f32).max(15f32), Stroke::new(2f32, Color32::RED));
+
+        let hovered = response.hover_pos().and_then(|pos| self.pixel_at(image_rect, image, pos));
+        let clicked = response.interact_pointer_pos()
+            .filter(|_| response.clicked())
+            .and_then(|pos| self.pixel_at(image_rect, image, pos));
+        CanvasResponse { hovered, clicked }
+    }
+}
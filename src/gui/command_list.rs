@@ -0,0 +1,131 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use eframe::egui::{Color32, RichText, ScrollArea, Ui};
+
+use crate::image::{CompactCommand, DrawCommand};
+use crate::utils::{store_to, try_load};
+
+const JUMP_TARGETS: [&str; 3] = ["Compose", "TryFill", "AddBitmap"];
+
+/// Scrollable list of all commands, only visible rows are laid out.
+pub struct CommandList {
+    // variants filtered out of the list
+    hidden: BTreeSet<&'static str>,
+    // rows passing the filter, recomputed when the filter or the commands change
+    visible: Vec<usize>,
+    visible_for: Option<(usize, BTreeSet<&'static str>)>,
+    // rows, persisted as plain command indices so that they survive recompression
+    bookmarks: BTreeSet<usize>,
+    // <out>/<task>/bookmarks.ron, None when commands don't come from a task
+    bookmarks_file: Option<PathBuf>,
+    scroll_to_current: bool,
+}
+
+impl CommandList {
+    pub fn new(bookmarks_file: Option<PathBuf>, offsets: &[usize]) -> Self {
+        let bookmarks = bookmarks_file.as_ref()
+            .and_then(try_load::<Vec<usize>, _>)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|idx| offsets.binary_search(&idx).ok())
+            .collect();
+        CommandList {
+            hidden: BTreeSet::new(),
+            visible: Vec::new(),
+            visible_for: None,
+            bookmarks,
+            bookmarks_file,
+            scroll_to_current: false,
+        }
+    }
+
+    /// Starts over for new commands, bookmarks are reloaded from and stored to the same file.
+    pub fn reset(&mut self, offsets: &[usize]) {
+        *self = CommandList::new(self.bookmarks_file.take(), offsets);
+    }
+
+    /// Makes the list scroll to the current command on the next frame.
+    pub fn follow_current(&mut self) {
+        self.scroll_to_current = true;
+    }
+
+    fn toggle_bookmark(&mut self, row: usize, offsets: &[usize]) {
+        if !self.bookmarks.remove(&row) {
+            self.bookmarks.insert(row);
+        }
+        if let Some(file) = &self.bookmarks_file {
+            let bookmarks: Vec<usize> = self.bookmarks.iter().map(|row| offsets[*row]).collect();
+            store_to(&bookmarks, file);
+        }
+    }
+
+    fn update_visible(&mut self, commands: &[CompactCommand]) {
+        let key = (commands.len(), self.hidden.clone());
+        if self.visible_for.as_ref() != Some(&key) {
+            self.visible = (0..commands.len())
+                .filter(|row| !self.hidden.contains(commands[*row].name()))
+                .collect();
+            self.visible_for = Some(key);
+        }
+    }
+
+    /// Returns the row to seek to, if the user asked for one.
+    pub fn show(&mut self, ui: &mut Ui, commands: &[CompactCommand], offsets: &[usize], current: usize) -> Option<usize> {
+        let mut seek = None;
+        ui.horizontal_wrapped(|ui| {
+            for name in JUMP_TARGETS {
+                if ui.small_button(format!("Next {}", name)).clicked() {
+                    seek = (current + 1..commands.len()).find(|row| commands[*row].name() == name);
+                }
+            }
+            if ui.small_button("Next bookmark").clicked() {
+                seek = self.bookmarks.range(current + 1..).next().cloned();
+            }
+            if ui.small_button("Prev bookmark").clicked() {
+                seek = self.bookmarks.range(..current).next_back().cloned();
+            }
+            let bookmarked = self.bookmarks.contains(&current);
+            if current < commands.len() && ui.small_button(if bookmarked { "Unbookmark" } else { "Bookmark" }).clicked() {
+                self.toggle_bookmark(current, offsets);
+            }
+        });
+        ui.collapsing("Filter", |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for name in DrawCommand::NAMES {
+                    let mut shown = !self.hidden.contains(name);
+                    if ui.checkbox(&mut shown, name).changed() {
+                        if shown {
+                            self.hidden.remove(name);
+                        } else {
+                            self.hidden.insert(name);
+                        }
+                    }
+                }
+            });
+        });
+
+        self.update_visible(commands);
+        let row_height = ui.spacing().interact_size.y;
+        let mut scroll_area = ScrollArea::vertical().id_source("command_list").auto_shrink([false, false]);
+        if self.scroll_to_current {
+            let position = self.visible.partition_point(|row| *row < current);
+            let spacing = ui.spacing().item_spacing.y;
+            scroll_area = scroll_area.vertical_scroll_offset((position as f32 - 5f32).max(0f32) * (row_height + spacing));
+            self.scroll_to_current = false;
+        }
+        scroll_area.show_rows(ui, row_height, self.visible.len(), |ui, range| {
+            for row in self.visible[range].iter().cloned() {
+                let mark = if self.bookmarks.contains(&row) { "*" } else { " " };
+                let mut text = RichText::new(format!("{}{:>8} {:?}", mark, row, commands[row])).monospace();
+                if row == current {
+                    text = text.color(Color32::RED);
+                }
+                if ui.selectable_label(row == current, text).clicked() {
+                    seek = Some(row);
+                }
+            }
+        });
+        seek
+    }
+}
@@ -0,0 +1,210 @@
+use eframe::egui::{Color32, DragValue, FontId, ScrollArea, Ui};
+use eframe::egui::text::{LayoutJob, TextFormat};
+
+use crate::interpreter::dna::{Base, Dna};
+
+const ROW: usize = 64;
+const WINDOW: usize = 16 * ROW;
+const MAX_LISTED: usize = 20;
+
+/// Markers from data/info.md with the colour used to highlight them.
+const MARKERS: [(&str, &str, Color32); 3] = [
+    ("IFPICFPPCCC", "marker", Color32::from_rgb(90, 90, 0)),
+    ("IFPICFPPCFFPP", "green zone", Color32::from_rgb(0, 90, 0)),
+    ("IFPICFPPCFIPP", "blue zone", Color32::from_rgb(0, 0, 120)),
+];
+
+fn bases(s: &str) -> Result<Vec<Base>, String> {
+    s.trim().chars().map(Base::from_char).collect()
+}
+
+fn base_color(base: Base) -> Color32 {
+    match base {
+        Base::I => Color32::LIGHT_BLUE,
+        Base::C => Color32::LIGHT_GREEN,
+        Base::F => Color32::from_rgb(255, 180, 80),
+        Base::P => Color32::from_rgb(255, 130, 200),
+    }
+}
+
+pub struct DnaInspector {
+    offset: usize,
+    search: String,
+    status: String,
+    // marker occurrences and the step they were located at
+    markers: Option<(usize, Vec<(usize, usize)>)>,
+}
+
+impl DnaInspector {
+    pub fn new() -> Self {
+        DnaInspector {
+            offset: 0,
+            search: String::new(),
+            status: String::new(),
+            markers: None,
+        }
+    }
+
+    fn find_next(&mut self, dna: &Dna) {
+        let target = match bases(&self.search) {
+            Ok(target) if !target.is_empty() => target,
+            Ok(_) => return,
+            Err(err) => {
+                self.status = err;
+                return;
+            }
+        };
+        let from = self.offset + 1;
+        match dna.find(from, &target) {
+            Some(end) => {
+                self.offset = from + end - target.len();
+                self.status = format!("Found at {}", self.offset);
+            }
+            None => {
+                self.status = format!("{} not found after {}", self.search.trim(), self.offset);
+            }
+        }
+    }
+
+    fn locate_markers(&mut self, dna: &Dna, step: usize) {
+        let mut found = Vec::new();
+        for (id, (marker, _, _)) in MARKERS.iter().enumerate() {
+            let marker = bases(marker).unwrap();
+            let mut from = 0;
+            while let Some(end) = dna.find(from, &marker) {
+                let pos = from + end - marker.len();
+                found.push((id, pos));
+                from = pos + 1;
+            }
+        }
+        self.markers = Some((step, found));
+    }
+
+    // Background of every base in the window, markers overlapping it are highlighted.
+    fn highlights(window: &[Base]) -> Vec<Color32> {
+        let mut result = vec![Color32::TRANSPARENT; window.len()];
+        for (marker, _, color) in MARKERS {
+            let marker = bases(marker).unwrap();
+            for (pos, w) in window.windows(marker.len()).enumerate() {
+                if w == marker.as_slice() {
+                    result[pos..pos + marker.len()].iter_mut().for_each(|c| *c = color);
+                }
+            }
+        }
+        result
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, dna: &Dna, step: usize) {
+        let len = dna.len();
+        ui.horizontal(|ui| {
+            ui.label(format!("Offset (of {}):", len));
+            ui.add(DragValue::new(&mut self.offset).clamp_range(0..=len.saturating_sub(1)).speed(ROW as f64));
+            if ui.button("<<").clicked() {
+                self.offset = self.offset.saturating_sub(WINDOW);
+            }
+            if ui.button(">>").clicked() {
+                self.offset = (self.offset + WINDOW).min(len.saturating_sub(1));
+            }
+            ui.label("Search:");
+            ui.text_edit_singleline(&mut self.search);
+            if ui.button("Find next").clicked() {
+                self.find_next(dna);
+            }
+            if ui.button("Locate markers").clicked() {
+                self.locate_markers(dna, step);
+            }
+            ui.label(&self.status);
+        });
+        ui.horizontal(|ui| {
+            for (marker, name, color) in MARKERS {
+                let mut job = LayoutJob::default();
+                job.append(&format!("{} {}", name, marker), 0f32, TextFormat {
+                    font_id: FontId::monospace(12f32),
+                    color: Color32::WHITE,
+                    background: color,
+                    ..Default::default()
+                });
+                ui.label(job);
+            }
+        });
+        if let Some((located_at, markers)) = &self.markers {
+            let mut jump = None;
+            ui.horizontal_wrapped(|ui| {
+                ui.label(format!("Markers at step {}:", located_at));
+                for (id, (_, name, _)) in MARKERS.iter().enumerate() {
+                    for (_, pos) in markers.iter().filter(|(m, _)| *m == id).take(MAX_LISTED) {
+                        if ui.small_button(format!("{} @{}", name, pos)).clicked() {
+                            jump = Some(*pos);
+                        }
+                    }
+                }
+            });
+            if let Some(pos) = jump {
+                self.offset = pos;
+            }
+        }
+
+        // start at a row boundary so that offsets in the margin stay round
+        let start = self.offset / ROW * ROW;
+        let window = dna.subseq(start..(start + WINDOW).min(len));
+        let window = window.to_vec(0..window.len());
+        let highlights = DnaInspector::highlights(&window);
+        ScrollArea::vertical().id_source("dna_window").show(ui, |ui| {
+            for (row, chunk) in window.chunks(ROW).enumerate() {
+                let row_start = start + row * ROW;
+                let mut job = LayoutJob::default();
+                let font_id = FontId::monospace(12f32);
+                job.append(&format!("{:>9} ", row_start), 0f32, TextFormat {
+                    font_id: font_id.clone(),
+                    ..Default::default()
+                });
+                for (i, base) in chunk.iter().enumerate() {
+                    let pos = row_start + i;
+                    job.append(&base.to_char().to_string(), 0f32, TextFormat {
+                        font_id: font_id.clone(),
+                        color: if pos == self.offset { Color32::RED } else { base_color(*base) },
+                        background: highlights[row * ROW + i],
+                        ..Default::default()
+                    });
+                }
+                ui.label(job);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inspector(search: &str, offset: usize) -> DnaInspector {
+        let mut inspector = DnaInspector::new();
+        inspector.search = search.to_string();
+        inspector.offset = offset;
+        inspector
+    }
+
+    #[test]
+    fn find_next_test() {
+        let dna = Dna::from_string("ICFPICFP").unwrap();
+        let mut found = inspector("ICF", 0);
+        found.find_next(&dna);
+        assert_eq!(found.offset, 4);
+        found.find_next(&dna);
+        assert_eq!(found.offset, 4);
+        assert_eq!(found.status, "ICF not found after 4");
+        // searching from past the end finds nothing
+        let mut at_end = inspector("P", dna.len() - 1);
+        at_end.find_next(&dna);
+        assert_eq!(at_end.offset, dna.len() - 1);
+        inspector("P", 0).find_next(&Dna::from_string("").unwrap());
+    }
+
+    #[test]
+    fn locate_markers_test() {
+        let dna = Dna::from_string("IFPICFPPCCCIFPICFPPCCC").unwrap();
+        let mut inspector = DnaInspector::new();
+        inspector.locate_markers(&dna, 7);
+        assert_eq!(inspector.markers, Some((7, vec![(0, 0), (0, 11)])));
+    }
+}
@@ -0,0 +1,204 @@
+use std::path::PathBuf;
+
+use eframe::egui::{ComboBox, DragValue, ScrollArea, Ui};
+
+use crate::image::DrawCommand;
+use crate::interpreter::interpreter::{Context, do_step_traced, Step};
+use crate::interpreter::runner::dna_for_prefix;
+
+/// What the drawer pane should do after the interpreter pane was shown.
+pub enum InterpreterUpdate {
+    // a new prefix was loaded, previous commands are no longer relevant
+    Reset,
+    // freshly emitted commands together with the steps which emitted them
+    Commands(Vec<(DrawCommand, usize)>),
+}
+
+// steps done per frame by "Run N" and "Run until", so the window keeps responding
+const STEPS_PER_FRAME: usize = 10_000;
+
+/// "Run N" or "Run until" in progress, advanced by a batch of steps every frame.
+#[derive(Clone, Copy)]
+struct Running {
+    steps_left: usize,
+    // None runs all the steps, Some(None) stops at any valid RNA, Some(Some(name)) at that command
+    until: Option<Option<&'static str>>,
+}
+
+impl Running {
+    fn stops_at(&self, command: &DrawCommand) -> bool {
+        match self.until {
+            None => false,
+            Some(None) => true,
+            Some(Some(name)) => command.name() == name,
+        }
+    }
+}
+
+pub struct InterpreterPane {
+    // Endo's DNA is read from here when a prefix is loaded
+    data_dir: PathBuf,
+    prefix: String,
+    context: Option<Context>,
+    last_step: Option<Step>,
+    status: String,
+    run_count: usize,
+    // None stands for any valid RNA
+    until: Option<&'static str>,
+    // how many rna of the context were already passed to the drawer
+    rna_seen: usize,
+    running: Option<Running>,
+}
+
+impl InterpreterPane {
+    pub fn new(data_dir: PathBuf) -> Self {
+        InterpreterPane {
+            data_dir,
+            prefix: String::new(),
+            context: None,
+            last_step: None,
+            status: String::new(),
+            run_count: 1000,
+            until: None,
+            rna_seen: 0,
+            running: None,
+        }
+    }
+
+    pub fn context(&self) -> Option<&Context> {
+        self.context.as_ref()
+    }
+
+    fn load(&mut self) {
+        match dna_for_prefix(&self.data_dir, self.prefix.trim()) {
+            Ok(dna) => {
+                self.context = Some(Context::new(dna));
+                self.status = "Loaded".to_string();
+            }
+            Err(err) => {
+                self.context = None;
+                self.status = err;
+            }
+        }
+        self.last_step = None;
+        self.rna_seen = 0;
+        self.running = None;
+    }
+
+    // Runs at most `max_steps` steps, stops earlier if a step emits a command accepted by `stop`.
+    // Returns whether it stopped earlier or the DNA is finished.
+    fn run<F: Fn(&DrawCommand) -> bool>(&mut self, max_steps: usize, stop: F) -> bool {
+        let context = match &mut self.context {
+            Some(context) => context,
+            None => return true,
+        };
+        for _ in 0..max_steps {
+            let rna_before = context.rna.len();
+            match do_step_traced(context) {
+                Ok(step) => {
+                    self.last_step = Some(step);
+                    self.status = format!("Step: {}", context.step);
+                }
+                Err(reason) => {
+                    self.status = format!("Finished on step {}: {}", context.step, reason);
+                    return true;
+                }
+            }
+            if context.rna[rna_before..].iter().filter_map(|rna| DrawCommand::decode(rna)).any(|c| stop(&c)) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn run_batch(&mut self, ui: &mut Ui) {
+        if let Some(mut running) = self.running.take() {
+            let batch = running.steps_left.min(STEPS_PER_FRAME);
+            running.steps_left -= batch;
+            if !self.run(batch, |command| running.stops_at(command)) && running.steps_left > 0 {
+                self.running = Some(running);
+                ui.ctx().request_repaint();
+            }
+        }
+    }
+
+    fn new_commands(&mut self) -> Vec<(DrawCommand, usize)> {
+        let context = match &self.context {
+            Some(context) => context,
+            None => return Vec::new(),
+        };
+        let commands = context.rna[self.rna_seen..].iter()
+            .zip(&context.rna_steps[self.rna_seen..])
+            .filter_map(|(rna, step)| DrawCommand::decode(rna).map(|command| (command, *step)))
+            .collect();
+        self.rna_seen = context.rna.len();
+        commands
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) -> Option<InterpreterUpdate> {
+        self.run_batch(ui);
+        let mut reset = false;
+        ui.horizontal(|ui| {
+            ui.label("Prefix:");
+            ui.text_edit_singleline(&mut self.prefix);
+            if ui.button("Load").clicked() {
+                self.load();
+                reset = true;
+            }
+        });
+        ui.add_enabled_ui(self.context.is_some() && self.running.is_none(), |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Step").clicked() {
+                    self.run(1, |_| false);
+                }
+                ui.add(DragValue::new(&mut self.run_count).clamp_range(1..=1_000_000_000usize));
+                if ui.button("Run N").clicked() {
+                    self.running = Some(Running { steps_left: self.run_count, until: None });
+                }
+            });
+            ui.horizontal(|ui| {
+                ComboBox::from_id_source("run_until")
+                    .selected_text(self.until.unwrap_or("Any RNA"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.until, None, "Any RNA");
+                        for name in DrawCommand::NAMES {
+                            ui.selectable_value(&mut self.until, Some(name), name);
+                        }
+                    });
+                if ui.button("Run until (at most N steps)").clicked() {
+                    self.running = Some(Running { steps_left: self.run_count, until: Some(self.until) });
+                }
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.label(&self.status);
+            if self.running.is_some() && ui.button("Stop").clicked() {
+                self.running = None;
+            }
+        });
+        if let Some(context) = &self.context {
+            ui.label(format!("DNA length: {}, RNA: {}", context.dna.len(), context.rna.len()));
+        }
+        if let Some(step) = &self.last_step {
+            ScrollArea::vertical().id_source("last_step").max_height(300f32).show(ui, |ui| {
+                ui.monospace(format!("Pat: {:?}", step.pattern));
+                ui.monospace(format!("Tmp: {:?}", step.template));
+                match &step.env {
+                    Some(env) => ui.monospace(format!("Env:\n{:?}", env)),
+                    None => ui.monospace("No match"),
+                };
+            });
+        }
+
+        if reset {
+            Some(InterpreterUpdate::Reset)
+        } else {
+            let commands = self.new_commands();
+            if commands.is_empty() {
+                None
+            } else {
+                Some(InterpreterUpdate::Commands(commands))
+            }
+        }
+    }
+}
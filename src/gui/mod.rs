@@ -0,0 +1,288 @@
+use std::path::PathBuf;
+
+use eframe::{egui::{self, Color32, Event, Key, ColorImage, TextureHandle}, HardwareAcceleration, Theme};
+use eframe::egui::{vec2, Image, Sense, Slider};
+use image::RgbaImage;
+
+use crate::{cli::Args, image::{compress, CompactCommand, DrawCommand}, session::Session};
+
+use canvas::{Canvas, CanvasResponse};
+use command_list::CommandList;
+use dna_inspector::DnaInspector;
+use interpreter_pane::{InterpreterPane, InterpreterUpdate};
+use target_overlay::TargetOverlay;
+
+mod canvas;
+mod command_list;
+mod dna_inspector;
+mod interpreter_pane;
+mod target_overlay;
+
+struct GuiImage {
+    texture_handle: TextureHandle,
+    width: f32,
+    height: f32,
+}
+
+pub struct EndoApp {
+    session: Session,
+    images: Vec<GuiImage>,
+    // session generation the textures were built for
+    images_generation: u64,
+
+    painted_by: Option<String>,
+    canvas: Canvas,
+    hovered: Option<String>,
+    command_list: CommandList,
+    target_overlay: TargetOverlay,
+
+    interpreter: InterpreterPane,
+    dna_inspector: DnaInspector,
+}
+
+impl EndoApp {
+    fn new(data_dir: PathBuf, out_dir: Option<PathBuf>, commands: Vec<CompactCommand>, steps: Option<Vec<usize>>) -> Self {
+        // provenance for the "painted by" readout under the cursor
+        let session = Session::new(commands, steps, true);
+        let command_list = CommandList::new(out_dir.map(|dir| dir.join("bookmarks.ron")), session.offsets());
+        EndoApp {
+            session,
+            images: Vec::new(),
+            images_generation: 0,
+            painted_by: None,
+            canvas: Canvas::new(),
+            hovered: None,
+            command_list,
+            target_overlay: TargetOverlay::new(),
+            interpreter: InterpreterPane::new(data_dir),
+            dna_inspector: DnaInspector::new(),
+        }
+    }
+
+    fn apply_interpreter_update(&mut self, update: InterpreterUpdate) {
+        match update {
+            InterpreterUpdate::Reset => {
+                self.session.reset(Vec::new(), Some(Vec::new()));
+                self.command_list.reset(self.session.offsets());
+                self.painted_by = None;
+            }
+            InterpreterUpdate::Commands(commands) => {
+                self.session.push_commands(commands);
+            }
+        }
+    }
+
+    fn reload_bitmaps(&mut self, ctx: &egui::Context) {
+        if self.images_generation == self.session.generation() {
+            return;
+        }
+        self.images_generation = self.session.generation();
+        self.images = self.session.drawer().bitmaps.iter()
+            .map(|bitmap| load_image(ctx, bitmap))
+            .collect();
+    }
+
+    fn who_painted(&self, layer: usize, x: u32, y: u32) -> String {
+        match self.session.who_painted(layer, x, y) {
+            Some(painted_by) => format!("({}, {}) painted by #{} {:?}, step: {:?}",
+                                        x, y, painted_by.row, painted_by.command, painted_by.step),
+            None => format!("({}, {}) was never painted", x, y)
+        }
+    }
+}
+
+fn load_image(ctx: &egui::Context, image: &RgbaImage) -> GuiImage {
+    let pixels: Vec<Color32> = image
+        .pixels()
+        .map(|p| Color32::from_rgba_premultiplied(p.0[0], p.0[1], p.0[2], p.0[3]))
+        .collect();
+    let color_image = ColorImage {
+        size: [image.width() as usize, image.height() as usize],
+        pixels
+    };
+    let texture_handle = ctx.load_texture("Some image", color_image, egui::TextureFilter::Nearest);
+    GuiImage {
+        texture_handle,
+        width: image.width() as f32,
+        height: image.height() as f32,
+    }
+}
+
+impl eframe::App for EndoApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let events = if ctx.wants_keyboard_input() {
+            Vec::new()
+        } else {
+            ctx.input().events.clone()
+        };
+        for event in &events {
+            match event {
+                Event::Key { key: Key::ArrowDown, pressed: true, modifiers } => {
+                    let step = if modifiers.shift {
+                        100
+                    } else {
+                        1
+                    };
+                    self.session.step_forward(step);
+                    self.command_list.follow_current();
+                }
+                Event::Key { key: Key::ArrowUp, pressed: true, modifiers } => {
+                    let step = if modifiers.shift {
+                        100
+                    } else {
+                        1
+                    };
+                    self.session.step_back(step);
+                    self.command_list.follow_current();
+                }
+                Event::Key { key: Key::ArrowLeft, pressed: true, modifiers: _ } => {
+                    self.session.select_layer(|id| id.saturating_sub(1));
+                }
+                Event::Key { key: Key::ArrowRight, pressed: true, modifiers: _ } => {
+                    self.session.select_layer(|id| id.saturating_add(1));
+                }
+                _ => {}
+            }
+        }
+
+        let update = egui::SidePanel::right("interpreter").show(ctx, |ui| {
+            ui.heading("Interpreter");
+            self.interpreter.show(ui)
+        }).inner;
+        if let Some(update) = update {
+            self.apply_interpreter_update(update);
+        }
+        self.reload_bitmaps(ctx);
+        if let Some(context) = self.interpreter.context() {
+            let dna_inspector = &mut self.dna_inspector;
+            egui::TopBottomPanel::bottom("dna").resizable(true).show(ctx, |ui| {
+                ui.heading("DNA");
+                dna_inspector.show(ui, &context.dna, context.step);
+            });
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical(|ui| {
+                self.target_overlay.controls(ui);
+                ui.horizontal(|ui| {
+                    let mut selected = None;
+                    for (id, image) in self.images.iter().enumerate() {
+                        let response = ui.add(Image::new(&image.texture_handle, [image.width / 8f32, image.height / 8f32])
+                            .sense(Sense::click()));
+                        if self.session.current_layer() == id {
+                            ui.painter().rect_stroke(response.rect, 0f32, (2f32, Color32::RED));
+                        }
+                        if response.clicked() {
+                            selected = Some(id);
+                        }
+                    }
+                    if let Some(id) = selected {
+                        self.session.select_layer(|_| id);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let size = vec2((ui.available_width() - 320f32).max(200f32), 620f32);
+                    let id = self.session.current_layer();
+                    let mut canvas_response = None;
+                    if let Some(image) = self.images.get(id) {
+                        let drawer = self.session.drawer();
+                        self.target_overlay.refresh(ctx, self.session.generation(), id, &drawer.bitmaps[id]);
+                        let response = self.canvas.show(ui, size, &image.texture_handle, &drawer.bitmaps[id], drawer,
+                                                        self.target_overlay.overlay());
+                        self.hovered = response.hovered.map(|(x, y)| {
+                            let [r, g, b, a] = drawer.bitmaps[id].get_pixel(x, y).0;
+                            format!("({}, {}) RGBA: [{}, {}, {}, {}]", x, y, r, g, b, a)
+                        });
+                        canvas_response = Some((id, response));
+                    }
+                    if let Some((id, CanvasResponse { clicked: Some((x, y)), .. })) = canvas_response {
+                        self.painted_by = Some(self.who_painted(id, x, y));
+                    }
+                    let mut seek = None;
+                    ui.vertical(|ui| {
+                        if ui.button("Reset zoom").clicked() {
+                            self.canvas.reset();
+                        }
+                        seek = self.command_list.show(ui, self.session.commands(), self.session.offsets(),
+                                                     self.session.current_command());
+                    });
+                    if let Some(row) = seek {
+                        self.session.seek(row);
+                        self.command_list.follow_current();
+                    }
+                });
+                if let Some(hovered) = &self.hovered {
+                    ui.label(hovered);
+                }
+
+                let mut current_command = self.session.current_command();
+                if !self.session.commands().is_empty() &&
+                    ui.add(Slider::new(&mut current_command, 0..=(self.session.commands().len() - 1))).dragged() {
+                    self.session.seek(current_command);
+                    self.command_list.follow_current();
+                }
+                if let Some(painted_by) = &self.painted_by {
+                    ui.label(painted_by);
+                }
+            })
+        });
+        if self.images_generation != self.session.generation() {
+            // textures are rebuilt at the start of the next frame
+            ctx.request_repaint();
+        }
+    }
+}
+
+crate::entry_point!("gui", gui_main, _EP_GUI, "[task] [--data-dir <dir>] [--out <dir>]",
+                    "Opens the viewer for the task's commands, or an empty one driven by the interpreter pane");
+fn gui_main(args: &Args) -> Result<(), String> {
+    // without a task the gui starts empty, commands come from the interpreter pane
+    let out_dir = args.get("task").map(|task| {
+        println!("Gui for: {}", task);
+        args.out_dir().join(task)
+    });
+    let (commands, steps) = match &out_dir {
+        Some(out_dir) => {
+            let commands = match crate::utils::try_load(out_dir.join("compact.ron")) {
+                Some(commands) => commands,
+                None => {
+                    let commands: Vec<DrawCommand> = crate::utils::read(out_dir.join("commands.ron"))?;
+                    compress(&commands)
+                }
+            };
+            let steps = crate::utils::try_load(out_dir.join("steps.ron"));
+            (commands, steps)
+        }
+        None => (Vec::new(), Some(Vec::new())),
+    };
+    let data_dir = args.data_dir();
+    let native_options = eframe::NativeOptions {
+        always_on_top: false,
+        maximized: false,
+        decorated: true,
+        fullscreen: false,
+        drag_and_drop_support: true,
+        icon_data: None,
+        initial_window_pos: None,
+        initial_window_size: None,
+        min_window_size: None,
+        max_window_size: None,
+        resizable: true,
+        transparent: false,
+        vsync: false,
+        multisampling: 0,
+        depth_buffer: 0,
+        stencil_buffer: 0,
+        hardware_acceleration: HardwareAcceleration::Required,
+        renderer: Default::default(),
+        follow_system_theme: false,
+        default_theme: Theme::Dark,
+        run_and_return: false
+    };
+    eframe::run_native("Endo", native_options, Box::new(|cc| {
+        let mut app = EndoApp::new(data_dir, out_dir, commands, steps);
+        app.reload_bitmaps(&cc.egui_ctx);
+        Box::new(app)
+    }));
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use eframe::egui::{self, Color32, Slider, TextureHandle, Ui};
+use image::{ImageBuffer, Rgba, RgbaImage};
+
+use crate::image::load_target;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OverlayMode {
+    Off,
+    // target drawn over the bitmap with adjustable opacity
+    Onion,
+    // absolute per channel difference between the bitmap and the target
+    Difference,
+}
+
+/// Number of pixels with a different colour, transparency is ignored
+/// in the same way as when the final picture is compared to the target.
+pub fn mismatches(bitmap: &RgbaImage, target: &RgbaImage) -> usize {
+    bitmap.pixels().zip(target.pixels())
+        .filter(|(a, b)| a.0[..3] != b.0[..3])
+        .count()
+}
+
+fn difference(bitmap: &RgbaImage, target: &RgbaImage) -> RgbaImage {
+    ImageBuffer::from_fn(bitmap.width(), bitmap.height(), |x, y| {
+        let a = bitmap.get_pixel(x, y).0;
+        let b = target.get_pixel(x, y).0;
+        Rgba([
+            a[0].abs_diff(b[0]),
+            a[1].abs_diff(b[1]),
+            a[2].abs_diff(b[2]),
+            255,
+        ])
+    })
+}
+
+pub struct TargetOverlay {
+    target: Result<RgbaImage, String>,
+    target_texture: Option<TextureHandle>,
+    difference_texture: Option<TextureHandle>,
+    mode: OverlayMode,
+    opacity: f32,
+    mismatches: Option<usize>,
+    // (bitmaps generation, layer) the mismatches and the difference were computed for
+    computed_for: Option<(u64, usize)>,
+}
+
+impl TargetOverlay {
+    pub fn new() -> Self {
+        TargetOverlay {
+            target: load_target(),
+            target_texture: None,
+            difference_texture: None,
+            mode: OverlayMode::Off,
+            opacity: 0.5,
+            mismatches: None,
+            computed_for: None,
+        }
+    }
+
+    /// Recomputes mismatches (and the difference image if it is shown) when the shown bitmap changes.
+    pub fn refresh(&mut self, ctx: &egui::Context, generation: u64, layer: usize, bitmap: &RgbaImage) {
+        let target = match &self.target {
+            Ok(target) => target,
+            Err(_) => return,
+        };
+        if self.computed_for != Some((generation, layer)) {
+            self.mismatches = Some(mismatches(bitmap, target));
+            self.difference_texture = None;
+            self.computed_for = Some((generation, layer));
+        }
+        if self.mode == OverlayMode::Onion && self.target_texture.is_none() {
+            self.target_texture = Some(super::load_image(ctx, target).texture_handle);
+        }
+        if self.mode == OverlayMode::Difference && self.difference_texture.is_none() {
+            self.difference_texture = Some(super::load_image(ctx, &difference(bitmap, target)).texture_handle);
+        }
+    }
+
+    /// Texture to draw over the bitmap and the tint to draw it with.
+    pub fn overlay(&self) -> Option<(&TextureHandle, Color32)> {
+        match self.mode {
+            OverlayMode::Off => None,
+            OverlayMode::Onion => self.target_texture.as_ref()
+                .map(|texture| (texture, Color32::from_white_alpha((self.opacity * 255f32) as u8))),
+            OverlayMode::Difference => self.difference_texture.as_ref()
+                .map(|texture| (texture, Color32::WHITE)),
+        }
+    }
+
+    pub fn controls(&mut self, ui: &mut Ui) {
+        if let Err(err) = &self.target {
+            ui.label(format!("Target: {}", err));
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Target:");
+            ui.selectable_value(&mut self.mode, OverlayMode::Off, "Off");
+            ui.selectable_value(&mut self.mode, OverlayMode::Onion, "Onion skin");
+            ui.selectable_value(&mut self.mode, OverlayMode::Difference, "Difference");
+            if self.mode == OverlayMode::Onion {
+                ui.add(Slider::new(&mut self.opacity, 0f32..=1f32).text("opacity"));
+            }
+            if let Some(mismatches) = self.mismatches {
+                ui.label(format!("Mismatched pixels: {}", mismatches));
+            }
+        });
+    }
+}
@@ -83,6 +83,26 @@ pub enum DrawCommand {
 }
 
 impl DrawCommand {
+    /// All values `name` can return.
+    pub const NAMES: [&'static str; 11] = ["AddColor", "ClearBucket", "Move", "TurnCC", "TurnC",
+        "Mark", "Line", "TryFill", "AddBitmap", "Compose", "Clip"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DrawCommand::AddColor(_) => "AddColor",
+            DrawCommand::ClearBucket => "ClearBucket",
+            DrawCommand::Move => "Move",
+            DrawCommand::TurnCC => "TurnCC",
+            DrawCommand::TurnC => "TurnC",
+            DrawCommand::Mark => "Mark",
+            DrawCommand::Line => "Line",
+            DrawCommand::TryFill => "TryFill",
+            DrawCommand::AddBitmap => "AddBitmap",
+            DrawCommand::Compose => "Compose",
+            DrawCommand::Clip => "Clip",
+        }
+    }
+
     pub fn decode(d: &[Base]) -> Option<Self> {
         use crate::interpreter::dna::Base::*;
         match d {
@@ -115,11 +135,101 @@ impl DrawCommand {
     }
 }
 
+/// Run-length folded form of a `DrawCommand` stream, see `compress` and `expand`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CompactCommand {
+    Command(DrawCommand),
+    MoveN(u32),
+    AddColorN(Color, u32),
+}
+
+impl From<DrawCommand> for CompactCommand {
+    fn from(command: DrawCommand) -> Self {
+        CompactCommand::Command(command)
+    }
+}
+
+impl CompactCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompactCommand::Command(command) => command.name(),
+            CompactCommand::MoveN(_) => "Move",
+            CompactCommand::AddColorN(_, _) => "AddColor",
+        }
+    }
+
+    /// Number of plain commands this command stands for.
+    pub fn len(&self) -> usize {
+        match self {
+            CompactCommand::Command(_) => 1,
+            CompactCommand::MoveN(n) => *n as usize,
+            CompactCommand::AddColorN(_, n) => *n as usize,
+        }
+    }
+
+    /// A run of zero commands, `compress` never produces one.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub fn compress(commands: &[DrawCommand]) -> Vec<CompactCommand> {
+    let mut result = Vec::new();
+    let mut rest = commands;
+    while let Some(first) = rest.first() {
+        let run = rest.iter()
+            .take(u32::MAX as usize)
+            .take_while(|command| *command == first)
+            .count();
+        match *first {
+            DrawCommand::Move if run > 1 => {
+                result.push(CompactCommand::MoveN(run as u32));
+            }
+            DrawCommand::AddColor(color) if run > 1 => {
+                result.push(CompactCommand::AddColorN(color, run as u32));
+            }
+            _ => {
+                result.extend(rest[..run].iter().map(|command| CompactCommand::Command(*command)));
+            }
+        }
+        rest = &rest[run..];
+    }
+    result
+}
+
+pub fn expand(commands: &[CompactCommand]) -> Vec<DrawCommand> {
+    commands.iter().flat_map(|command| {
+        let (command, n) = match *command {
+            CompactCommand::Command(command) => (command, 1),
+            CompactCommand::MoveN(n) => (DrawCommand::Move, n as usize),
+            CompactCommand::AddColorN(color, n) => (DrawCommand::AddColor(color), n as usize),
+        };
+        std::iter::repeat(command).take(n)
+    }).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use image::Rgba;
 
+    #[test]
+    fn compress_test() {
+        let red = DrawCommand::AddColor(Color::Rgb(Rgb::Red));
+        let blue = DrawCommand::AddColor(Color::Rgb(Rgb::Blue));
+        let commands = vec![DrawCommand::Move, DrawCommand::Move, DrawCommand::Move,
+                            red, red, blue, DrawCommand::Line, DrawCommand::Line, DrawCommand::Move];
+        let compact = compress(&commands);
+        assert_eq!(compact, vec![CompactCommand::MoveN(3),
+                                 CompactCommand::AddColorN(Color::Rgb(Rgb::Red), 2),
+                                 CompactCommand::Command(blue),
+                                 CompactCommand::Command(DrawCommand::Line),
+                                 CompactCommand::Command(DrawCommand::Line),
+                                 CompactCommand::Command(DrawCommand::Move)]);
+        assert_eq!(compact.iter().map(|c| c.len()).sum::<usize>(), commands.len());
+        assert_eq!(expand(&compact), commands);
+    }
+
     #[test]
     fn load_test() {
         let image = load_source().unwrap();
@@ -1,42 +1,79 @@
-use std::io::{Read, stdin, stdout, Write};
-use std::path::Path;
-use crate::interpreter::dna::Dna;
-
+use std::io::{stdin, BufRead};
+use crate::cli::Args;
+use crate::image::DrawCommand;
+use crate::interpreter::debugger::{Command, Debugger, Stop, HELP};
 use crate::interpreter::interpreter::Context;
-use crate::interpreter::match_replace::{match_pat, replace};
-use crate::interpreter::pattern::pattern;
-use crate::interpreter::template::template;
-
-fn read_dna<P: AsRef<Path>>(path: P) -> Dna {
-    let dna_str = &std::fs::read_to_string(path).unwrap();
-    return Dna::from_string(&dna_str).unwrap();
-}
-
-fn dna_for_prefix(dna_prefix: &str) -> Dna {
-    let prefix_dna = Dna::from_string(&dna_prefix).unwrap();
-    let endo_dna = read_dna("data/endo/dna");
-    return prefix_dna.concat(&endo_dna);
-}
+use crate::interpreter::runner::dna_for_prefix;
 
-crate::entry_point!("cmd", interpreter_cmd);
-fn interpreter_cmd() {
+crate::entry_point!("cmd", interpreter_cmd, "[--data-dir <dir>]",
+                    "Debugs Endo's DNA with a prefix read from stdin: breakpoints, watchpoints and stepping (type help)");
+fn interpreter_cmd(args: &Args) -> Result<(), String> {
     println!("Please enter DNA prefix:");
-    let mut dna_prefix = String::new();
-    stdin().read_line(&mut dna_prefix).unwrap();
+    let mut lines = stdin().lock().lines();
+    let dna_prefix = lines.next().unwrap_or_else(|| Ok(String::new())).map_err(|err| err.to_string())?;
     println!("Prefix: {:?}", dna_prefix);
 
-    let mut context = Context::new(dna_for_prefix(&dna_prefix.trim()));
+    let mut debugger = Debugger::new(Context::new(dna_for_prefix(args.data_dir(), dna_prefix.trim())?));
 
-    loop {
-        let p = pattern(&mut context).unwrap();
-        println!("Pat: {:?}", p);
-        let t = template(&mut context).unwrap();
-        println!("Tmp: {:?}", t);
-        if let Some(env) = match_pat(&mut context, p) {
-            println!("Env:\n{:?}", env);
-            replace(&mut context, t, env);
+    for line in lines {
+        let line = line.map_err(|err| err.to_string())?;
+        let command = match Command::parse(&line) {
+            Ok(command) => command,
+            Err(err) => {
+                println!("{}", err);
+                continue;
+            }
+        };
+        match command {
+            Command::Break(breakpoint) => {
+                println!("Breakpoint {}: {}", debugger.add(breakpoint.clone()), breakpoint);
+            }
+            Command::Delete(idx) => match debugger.delete(idx) {
+                Ok(breakpoint) => println!("Deleted {}", breakpoint),
+                Err(err) => println!("{}", err),
+            },
+            Command::Breakpoints => {
+                for (id, breakpoint) in debugger.breakpoints() {
+                    println!("{}: {}", id, breakpoint);
+                }
+            }
+            Command::Continue => {
+                let stop = debugger.run(None);
+                print_stop(&debugger, stop);
+            }
+            Command::Step(n) => {
+                let stop = debugger.run(Some(n));
+                print_stop(&debugger, stop);
+            }
+            Command::Env => match debugger.env() {
+                Some(env) => print!("{:?}", env),
+                None => println!("The last step matched nothing"),
+            },
+            Command::Dna { offset, len } => println!("{:?}", debugger.dna(offset, len)),
+            Command::Rna(n) => {
+                let context = &debugger.context;
+                let from = context.rna.len().saturating_sub(n);
+                for (rna, step) in context.rna[from..].iter().zip(&context.rna_steps[from..]) {
+                    let bases: String = rna.iter().map(|b| b.to_char()).collect();
+                    println!("{:>8} {} {}", step, bases, DrawCommand::decode(rna).map_or("", |command| command.name()));
+                }
+            }
+            Command::Help => println!("{}", HELP),
+            Command::Quit => break,
         }
-        stdin().read(&mut [0u8]).unwrap();
     }
+    Ok(())
+}
 
-}
\ No newline at end of file
+fn print_stop(debugger: &Debugger, stop: Stop) {
+    if let Some(step) = debugger.last() {
+        println!("Pat: {:?}", step.pattern);
+        println!("Tmp: {:?}", step.template);
+    }
+    let step = debugger.context.step;
+    match stop {
+        Stop::Breakpoint(id) => println!("Step {}: breakpoint {}: {}", step, id, debugger.breakpoint(id).unwrap()),
+        Stop::Steps => println!("Step {}", step),
+        Stop::Finished(reason) => println!("Step {}: finished with {}", step, reason),
+    }
+}
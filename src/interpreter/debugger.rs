@@ -0,0 +1,379 @@
+//! Breakpoints and watchpoints over `do_step_traced`, driven by the `cmd` REPL.
+//! Every condition is checked after a step, so a stop shows the step which caused it.
+use std::fmt::{Display, Formatter};
+use std::time::Instant;
+
+use crate::image::DrawCommand;
+use crate::interpreter::dna::{Base, Dna};
+use crate::interpreter::interpreter::{do_step_traced, Context, Step};
+use crate::interpreter::match_replace::Environment;
+use crate::interpreter::pattern::PItem;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Breakpoint {
+    /// Once `n` steps are done.
+    Step(usize),
+    /// After a step which emits rna, or the given draw command.
+    Rna(Option<DrawCommand>),
+    /// After a step whose pattern searches for bases containing these.
+    Search(Vec<Base>),
+    /// After a step which changes `len` bases at `offset` after the first occurrence of `marker`.
+    Watch { marker: Vec<Base>, offset: usize, len: usize },
+}
+
+fn bases_to_string(bases: &[Base]) -> String {
+    bases.iter().map(|b| b.to_char()).collect()
+}
+
+fn parse_bases(s: &str) -> Result<Vec<Base>, String> {
+    if s.is_empty() {
+        return Err("Expected bases".to_string());
+    }
+    s.chars().map(Base::from_char).collect()
+}
+
+fn parse_number(s: Option<&str>, name: &str) -> Result<usize, String> {
+    let s = s.ok_or_else(|| format!("Missing <{}>", name))?;
+    s.parse().map_err(|_| format!("Invalid <{}>: {:?}", name, s))
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Step(n) => write!(f, "step {}", n),
+            Breakpoint::Rna(None) => write!(f, "rna"),
+            Breakpoint::Rna(Some(command)) => write!(f, "rna {}", ron::to_string(command).unwrap()),
+            Breakpoint::Search(s) => write!(f, "search {}", bases_to_string(s)),
+            Breakpoint::Watch { marker, offset, len } => {
+                write!(f, "watch {} {} {}", bases_to_string(marker), offset, len)
+            }
+        }
+    }
+}
+
+/// Commands of the REPL, an empty line is a single step.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Command {
+    Break(Breakpoint),
+    Delete(usize),
+    Breakpoints,
+    Continue,
+    Step(usize),
+    Env,
+    Dna { offset: usize, len: usize },
+    Rna(usize),
+    Help,
+    Quit,
+}
+
+pub const HELP: &str = "\
+break step <n>                  stop once n steps are done
+break rna [command]             stop after rna is emitted, or a draw command like Line or AddColor(Rgb(Red))
+break search <bases>            stop after a pattern searches for bases containing these
+watch <marker> <offset> <len>   stop when len bases at offset after the marker change
+delete <n>                      remove breakpoint n
+breakpoints                     list breakpoints
+continue                        run until a breakpoint or the end
+step [n]                        do n steps (default 1), stopping at breakpoints
+env                             environment matched by the last step
+dna <offset> <len>              bases of the dna
+rna [n]                         last n rna (default 10)
+quit";
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            None => Command::Step(1),
+            Some("break" | "b") => Command::Break(match words.next() {
+                Some("step") => Breakpoint::Step(parse_number(words.next(), "n")?),
+                Some("rna") => Breakpoint::Rna(match words.next() {
+                    Some(command) => Some(ron::from_str(command)
+                        .map_err(|_| format!("Unknown draw command {:?}, expected one of {:?}", command, DrawCommand::NAMES))?),
+                    None => None,
+                }),
+                Some("search") => Breakpoint::Search(parse_bases(words.next().unwrap_or_default())?),
+                other => return Err(format!("Unknown breakpoint {:?}, expected step, rna or search", other.unwrap_or_default())),
+            }),
+            Some("watch" | "w") => Command::Break(Breakpoint::Watch {
+                marker: parse_bases(words.next().unwrap_or_default())?,
+                offset: parse_number(words.next(), "offset")?,
+                len: parse_number(words.next(), "len")?,
+            }),
+            Some("delete" | "d") => Command::Delete(parse_number(words.next(), "n")?),
+            Some("breakpoints") => Command::Breakpoints,
+            Some("continue" | "c") => Command::Continue,
+            Some("step" | "s") => Command::Step(words.next().map_or(Ok(1), |n| parse_number(Some(n), "n"))?),
+            Some("env" | "e") => Command::Env,
+            Some("dna") => Command::Dna {
+                offset: parse_number(words.next(), "offset")?,
+                len: parse_number(words.next(), "len")?,
+            },
+            Some("rna" | "r") => Command::Rna(words.next().map_or(Ok(10), |n| parse_number(Some(n), "n"))?),
+            Some("help" | "h") => Command::Help,
+            Some("quit" | "q") => Command::Quit,
+            Some(other) => return Err(format!("Unknown command {:?}, try help", other)),
+        };
+        match words.next() {
+            Some(extra) => Err(format!("Unexpected {:?}", extra)),
+            None => Ok(command),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Stop {
+    /// Id of the breakpoint that was hit.
+    Breakpoint(usize),
+    Steps,
+    Finished(String),
+}
+
+// progress of long runs is printed every that many steps
+const PROGRESS_STEPS: usize = 10000;
+
+// where a watch's marker is and the bases it watches, as of the last step
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct Watched {
+    marker_at: Option<usize>,
+    region: Option<Vec<Base>>,
+}
+
+pub struct Debugger {
+    pub context: Context,
+    // indexed by id, deleted breakpoints leave None so that ids stay stable
+    breakpoints: Vec<Option<(Breakpoint, Watched)>>,
+    last: Option<Step>,
+    finished: Option<String>,
+}
+
+impl Debugger {
+    pub fn new(context: Context) -> Self {
+        Debugger { context, breakpoints: vec![], last: None, finished: None }
+    }
+
+    /// Breakpoints with their ids.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, &Breakpoint)> {
+        self.breakpoints.iter().enumerate()
+            .filter_map(|(id, breakpoint)| breakpoint.as_ref().map(|(breakpoint, _)| (id, breakpoint)))
+    }
+
+    pub fn breakpoint(&self, id: usize) -> Option<&Breakpoint> {
+        self.breakpoints.get(id)?.as_ref().map(|(breakpoint, _)| breakpoint)
+    }
+
+    pub fn add(&mut self, breakpoint: Breakpoint) -> usize {
+        let watched = match &breakpoint {
+            Breakpoint::Watch { marker, offset, len } => {
+                let marker_at = self.find_marker(marker, 0);
+                Watched { marker_at, region: self.region(marker, marker_at, *offset, *len) }
+            }
+            _ => Watched::default(),
+        };
+        self.breakpoints.push(Some((breakpoint, watched)));
+        self.breakpoints.len() - 1
+    }
+
+    pub fn delete(&mut self, id: usize) -> Result<Breakpoint, String> {
+        self.breakpoints.get_mut(id)
+            .and_then(|breakpoint| breakpoint.take())
+            .map(|(breakpoint, _)| breakpoint)
+            .ok_or_else(|| format!("No breakpoint {}", id))
+    }
+
+    /// Last step done, with its pattern, template and environment.
+    pub fn last(&self) -> Option<&Step> {
+        self.last.as_ref()
+    }
+
+    pub fn env(&self) -> Option<&Environment> {
+        self.last.as_ref().and_then(|step| step.env.as_ref())
+    }
+
+    // bases of the dna, clamped to its end
+    fn bases(&self, offset: usize, len: usize) -> Vec<Base> {
+        let end = offset.saturating_add(len).min(self.context.dna.len());
+        self.context.dna.to_vec(offset.min(end)..end)
+    }
+
+    pub fn dna(&self, offset: usize, len: usize) -> Dna {
+        Dna::from_slice(&self.bases(offset, len))
+    }
+
+    // the first occurrence of the marker which starts at `from` or later
+    fn find_marker(&self, marker: &[Base], from: usize) -> Option<usize> {
+        self.context.dna.find(from, marker).map(|end| from + end - marker.len())
+    }
+
+    // The marker after a step which replaced the first `removed` bases with `inserted` new ones,
+    // it is only searched for again if the step could have added an earlier one or removed it.
+    fn track_marker(&self, marker: &[Base], at: Option<usize>, removed: usize, inserted: usize) -> Option<usize> {
+        // occurrences which start in the inserted bases
+        let touched_end = (inserted + marker.len()).saturating_sub(1).min(self.context.dna.len());
+        if let Some(end) = self.context.dna.subseq(0..touched_end).find(0, marker) {
+            return Some(end - marker.len());
+        }
+        match at {
+            Some(at) if at >= removed => Some(at - removed + inserted),
+            Some(_) => self.find_marker(marker, inserted),
+            // the bases after the replaced ones had no marker before either
+            None => None,
+        }
+    }
+
+    // Bases of the watched region, None without the marker.
+    fn region(&self, marker: &[Base], marker_at: Option<usize>, offset: usize, len: usize) -> Option<Vec<Base>> {
+        Some(self.bases((marker_at? + marker.len()).saturating_add(offset), len))
+    }
+
+    /// Does up to `steps` steps, all of them with None, until a breakpoint is hit.
+    /// Progress is printed every 10000 steps.
+    pub fn run(&mut self, steps: Option<usize>) -> Stop {
+        let start_at = Instant::now();
+        let mut next_progress = PROGRESS_STEPS;
+        let mut done = 0;
+        loop {
+            if let Some(reason) = &self.finished {
+                return Stop::Finished(reason.clone());
+            }
+            if matches!(steps, Some(steps) if done >= steps) {
+                return Stop::Steps;
+            }
+            let rna_from = self.context.rna.len();
+            let len_before = self.context.dna.len();
+            match do_step_traced(&mut self.context) {
+                Ok(step) => self.last = Some(step),
+                Err(reason) => {
+                    self.finished = Some(reason);
+                    continue;
+                }
+            }
+            done += 1;
+            if done == next_progress {
+                println!("Step: {} Elapsed: {:?}", self.context.step, start_at.elapsed());
+                next_progress += PROGRESS_STEPS;
+            }
+            let kept = self.last.as_ref().unwrap().kept;
+            if let Some(hit) = self.hit(rna_from, len_before - kept, self.context.dna.len() - kept) {
+                return Stop::Breakpoint(hit);
+            }
+        }
+    }
+
+    // The first breakpoint hit by the last step, which replaced the first `removed` bases
+    // of the dna with `inserted` new ones. All watches are updated.
+    fn hit(&mut self, rna_from: usize, removed: usize, inserted: usize) -> Option<usize> {
+        let mut hit = None;
+        for id in 0..self.breakpoints.len() {
+            let (breakpoint, watched) = match &self.breakpoints[id] {
+                Some(breakpoint) => breakpoint,
+                None => continue,
+            };
+            let hits = match breakpoint {
+                Breakpoint::Step(n) => self.context.step == *n,
+                Breakpoint::Rna(None) => self.context.rna.len() > rna_from,
+                Breakpoint::Rna(Some(command)) => self.context.rna[rna_from..].iter()
+                    .any(|rna| DrawCommand::decode(rna).as_ref() == Some(command)),
+                Breakpoint::Search(bases) => self.last.iter().flat_map(|step| &step.pattern).any(|item| {
+                    matches!(item, PItem::Search { s } if bases.is_empty() || s.windows(bases.len()).any(|window| window == bases.as_slice()))
+                }),
+                Breakpoint::Watch { marker, offset, len } => {
+                    let marker_at = self.track_marker(marker, watched.marker_at, removed, inserted);
+                    let now = Watched { marker_at, region: self.region(marker, marker_at, *offset, *len) };
+                    let changed = now.region != watched.region;
+                    self.breakpoints[id].as_mut().unwrap().1 = now;
+                    changed
+                }
+            };
+            if hits && hit.is_none() {
+                hit = Some(id);
+            }
+        }
+        hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{Color, Rgb};
+    use crate::interpreter::dna::Base::*;
+    use crate::interpreter::pattern;
+    use crate::interpreter::template::{self, TItem};
+
+    const END: [Base; 3] = [I, I, C];
+
+    // (search) -> the same group, after emitting `rna` if any
+    fn gene(rna: &[Base], search: &[Base]) -> Vec<Base> {
+        let emit = if rna.is_empty() { vec![] } else { [&[I, I, I], rna].concat() };
+        [emit,
+         pattern::encode(&vec![PItem::Open, PItem::Search { s: search.to_vec() }, PItem::Close]), END.to_vec(),
+         template::encode(&vec![TItem::Ref { n: 0, l: 0 }]), END.to_vec()].concat()
+    }
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(Command::parse(""), Ok(Command::Step(1)));
+        assert_eq!(Command::parse("step 5"), Ok(Command::Step(5)));
+        assert_eq!(Command::parse("c"), Ok(Command::Continue));
+        assert_eq!(Command::parse("dna 10 20"), Ok(Command::Dna { offset: 10, len: 20 }));
+        assert_eq!(Command::parse("rna"), Ok(Command::Rna(10)));
+        assert_eq!(Command::parse("break rna AddColor(Rgb(Red))"),
+                   Ok(Command::Break(Breakpoint::Rna(Some(DrawCommand::AddColor(Color::Rgb(Rgb::Red)))))));
+        assert_eq!(Command::parse("break search ICF"), Ok(Command::Break(Breakpoint::Search(vec![I, C, F]))));
+        assert_eq!(Command::parse("watch PPP 2 3"),
+                   Ok(Command::Break(Breakpoint::Watch { marker: vec![P, P, P], offset: 2, len: 3 })));
+        assert!(Command::parse("break rna Jump").is_err());
+        assert!(Command::parse("break search").is_err());
+        assert!(Command::parse("dna 10").is_err());
+        assert!(Command::parse("env now").is_err());
+        let breakpoint = Breakpoint::Rna(Some(DrawCommand::AddColor(Color::Rgb(Rgb::Red))));
+        assert_eq!(Command::parse(&format!("break {}", breakpoint)), Ok(Command::Break(breakpoint)));
+    }
+
+    #[test]
+    fn breakpoints_test() {
+        let line = [P, F, F, I, C, C, P];
+        let red = [P, I, P, I, I, I, P];
+        let dna = [gene(&line, &[P, P, P, P]), gene(&red, &[I, C, P, P, P, P]), gene(&line, &[P, P, P, P]),
+                   vec![P, P, P, P, I, I, I, I]].concat();
+        let mut debugger = Debugger::new(Context::new(Dna::from_slice(&dna)));
+        assert_eq!(debugger.add(Breakpoint::Rna(Some(DrawCommand::AddColor(Color::Rgb(Rgb::Red))))), 0);
+        assert_eq!(debugger.add(Breakpoint::Search(vec![C, P])), 1);
+        assert_eq!(debugger.run(Some(1)), Stop::Steps);
+        assert_eq!(debugger.context.rna, vec![line.to_vec()]);
+        // both hit on the second step, the first one wins
+        assert_eq!(debugger.run(None), Stop::Breakpoint(0));
+        assert_eq!(debugger.context.step, 2);
+        assert_eq!(debugger.env().map(|env| env.len()), Some(1));
+        // ids stay the same after a delete
+        debugger.delete(0).unwrap();
+        assert!(debugger.delete(0).is_err());
+        assert_eq!(debugger.add(Breakpoint::Rna(None)), 2);
+        assert_eq!(debugger.breakpoints().map(|(id, _)| id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(debugger.run(None), Stop::Breakpoint(2));
+        assert_eq!(debugger.context.step, 3);
+        assert_eq!(debugger.dna(4, 100), Dna::from_slice(&[I, I, I, I]));
+        assert!(matches!(debugger.run(None), Stop::Finished(_)));
+        assert!(matches!(debugger.run(Some(1)), Stop::Finished(_)));
+    }
+
+    #[test]
+    fn step_and_watch_test() {
+        // the third gene quotes the two bases after the marker, the others leave the dna as it is
+        let quote = [pattern::encode(&vec![PItem::Open, PItem::Search { s: vec![P; 4] }, PItem::Close,
+                                           PItem::Open, PItem::Skip { n: 2 }, PItem::Close]), END.to_vec(),
+                     template::encode(&vec![TItem::Ref { n: 0, l: 0 }, TItem::Ref { n: 1, l: 1 }]), END.to_vec()].concat();
+        let dna = [gene(&[], &[P; 4]), gene(&[], &[P; 4]), quote, gene(&[], &[P; 4]), vec![P, P, P, P, I, C]].concat();
+        let mut debugger = Debugger::new(Context::new(Dna::from_slice(&dna)));
+        debugger.add(Breakpoint::Step(1));
+        debugger.add(Breakpoint::Watch { marker: vec![P; 4], offset: 0, len: 2 });
+        assert_eq!(debugger.run(None), Stop::Breakpoint(0));
+        assert_eq!(debugger.context.step, 1);
+        assert_eq!(debugger.run(None), Stop::Breakpoint(1));
+        assert_eq!(debugger.context.step, 3);
+        let watched = &debugger.breakpoints[1].as_ref().unwrap().1;
+        assert_eq!(watched.marker_at, debugger.find_marker(&[P; 4], 0));
+        assert_eq!(watched.region, Some(vec![C, F]));
+    }
+}
@@ -1,9 +1,8 @@
 use std::fmt::Write;
-use std::iter::Skip;
-use crate::interpreter::rope::{MAX_LEAF, Seq, SeqIter};
+use crate::interpreter::packed::{self, Bases, PackedSeq, MAX_LEAF};
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum Base {
     I, C, F, P
 }
@@ -40,12 +39,12 @@ pub type ShortDna = Vec<Base>;
 #[derive(Clone)]
 pub struct Dna {
     skipped: usize,
-    seq: Seq<Base>
+    seq: PackedSeq
 }
 
 impl std::fmt::Debug for Dna {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for b in self.seq.into_iter() {
+        for b in self {
             let c = match b {
                 Base::I => 'I',
                 Base::C => 'C',
@@ -65,7 +64,7 @@ impl std::fmt::Debug for Dna {
 
 impl Dna {
     pub fn empty() -> Self {
-        Dna { skipped: 0, seq: Seq::from_slice(&[]) }
+        Dna { skipped: 0, seq: PackedSeq::from_slice(&[]) }
     }
 
     pub fn from_string(s: &str) -> Result<Self, String> {
@@ -74,12 +73,12 @@ impl Dna {
         }).collect();
         Ok(Dna{
             skipped: 0,
-            seq: Seq::from_slice(data?.as_slice())
+            seq: PackedSeq::from_slice(data?.as_slice())
         })
     }
 
     pub fn from_slice(s: &[Base]) -> Self {
-        Dna { skipped: 0, seq: Seq::from_slice(s) }
+        Dna { skipped: 0, seq: PackedSeq::from_slice(s) }
     }
 
     pub fn prefix(&self, size: usize) -> Vec<Base> {
@@ -87,7 +86,8 @@ impl Dna {
     }
 
     pub fn skip(&mut self, count: usize) {
-        self.skipped += count;
+        // skipping past the end leaves the dna empty, as in the spec
+        self.skipped = (self.skipped + count).min(self.seq.len());
         if self.skipped > MAX_LEAF {
             self.seq = self.seq.subseq(self.skipped..);
             self.skipped = 0;
@@ -95,20 +95,19 @@ impl Dna {
     }
 
     pub fn to_vec(&self, range: std::ops::Range<usize>) -> Vec<Base> {
-        self.seq.into_iter()
-            .skip(self.skipped)
-            .skip(range.start)
-            .take(range.count())
-            .cloned()
-            .collect()
+        self.seq.bases((range.start + self.skipped)..(range.end + self.skipped)).collect()
     }
 
     pub fn nth(&self, idx: usize) -> Option<Base> {
-        self.seq.nth(idx + self.skipped).cloned()
+        self.seq.nth(idx + self.skipped)
     }
 
     pub fn len(&self) -> usize {
-        self.seq.len()
+        self.seq.len() - self.skipped
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
     pub fn subseq(&self, range: std::ops::Range<usize>) -> Dna {
@@ -119,14 +118,44 @@ impl Dna {
     pub fn concat(&self, other: &Self) -> Self {
         Dna { skipped: 0, seq: self.seq.subseq(self.skipped..).concat(&other.seq.subseq(other.skipped..)) }
     }
+
+    /// Index right after the first occurrence of `target` at or after `from`, relative to `from`.
+    pub fn find(&self, from: usize, target: &[Base]) -> Option<usize> {
+        packed::find(&self.seq, from + self.skipped, target)
+    }
+
+    /// The first (up to 32) bases packed in a word, and how many there are.
+    pub fn head(&self) -> (u64, usize) {
+        self.seq.word(self.skipped)
+    }
+
+    pub fn starts_with(&self, prefix: &Dna) -> bool {
+        self.len() >= prefix.len() &&
+            packed::same_bases(self.seq.words(self.skipped..self.skipped + prefix.len()),
+                               prefix.seq.words(prefix.skipped..prefix.seq.len()))
+    }
+
+    pub fn quote(&self) -> Dna {
+        Dna { skipped: 0, seq: packed::quote(&self.seq, self.skipped..self.seq.len()) }
+    }
 }
 
+impl PartialEq for Dna {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() &&
+            packed::same_bases(self.seq.words(self.skipped..self.seq.len()),
+                               other.seq.words(other.skipped..other.seq.len()))
+    }
+}
+
+impl Eq for Dna {}
+
 impl <'a> IntoIterator for &'a Dna {
-    type Item = &'a Base;
-    type IntoIter = Skip<SeqIter<'a, Base>>;
+    type Item = Base;
+    type IntoIter = Bases<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.seq.into_iter().skip(self.skipped)
+        self.seq.bases(self.skipped..self.seq.len())
     }
 }
 
@@ -0,0 +1,251 @@
+//! Checks shared by the fuzz targets in `fuzz/` and the deterministic driver below.
+//! Every check takes arbitrary bytes, must never panic on its own, and asserts
+//! the invariants of the decoders and of matching.
+//!
+//! With cargo-fuzz: `cd fuzz && cargo fuzz run <target> corpus/<target>`.
+//! Offline, without libfuzzer: `cargo run fuzz-replay <target> [dir]`.
+use std::path::{Path, PathBuf};
+
+use crate::cli::Args;
+use crate::interpreter::dna::{Base, Dna, ShortDna};
+use crate::interpreter::interpreter::{self, Context};
+use crate::interpreter::literals::{asnat, consts, nat, protect};
+use crate::interpreter::match_replace::{match_pat, replace};
+use crate::interpreter::pattern::{self, Pattern, PItem};
+use crate::interpreter::reference;
+use crate::interpreter::template::{self, TItem};
+
+/// Takes arbitrary bytes and panics on a broken invariant.
+pub type Check = fn(&[u8]);
+
+pub const TARGETS: [(&str, Check); 6] = [
+    ("nat", check_nat),
+    ("consts", check_consts),
+    ("pattern", check_pattern),
+    ("template", check_template),
+    ("match", check_match),
+    ("step", check_step),
+];
+
+/// Letters of the DNA alphabet map to themselves, so corpus files can be plain DNA.
+pub fn bases(data: &[u8]) -> Vec<Base> {
+    use Base::*;
+    data.iter().map(|b| match b {
+        b'I' => I,
+        b'C' => C,
+        b'F' => F,
+        b'P' => P,
+        other => [I, C, F, P][(other % 4) as usize],
+    }).collect()
+}
+
+// runs a decoder, returns its result, the bases it consumed and the rna it emitted
+fn decode<T, F>(dna: &[Base], f: F) -> (T, Vec<Base>, Vec<ShortDna>)
+where
+    F: FnOnce(&mut Context) -> T,
+{
+    let mut context = Context::new(Dna::from_slice(dna));
+    let result = f(&mut context);
+    let consumed = dna[..dna.len() - context.dna.len()].to_vec();
+    (result, consumed, context.rna)
+}
+
+pub fn check_nat(data: &[u8]) {
+    let dna = bases(data);
+    let (n, consumed, _) = decode(&dna, nat);
+    if let Ok(n) = n {
+        assert_eq!(consumed.last(), Some(&Base::P));
+        let encoded = asnat(n);
+        assert!(encoded.len() <= consumed.len());
+        let (decoded, reconsumed, _) = decode(&encoded, nat);
+        assert_eq!(decoded, Ok(n));
+        assert_eq!(reconsumed, encoded);
+    }
+}
+
+pub fn check_consts(data: &[u8]) {
+    let dna = bases(data);
+    let (s, consumed, _) = decode(&dna, consts);
+    // quoting is the exact inverse of consts
+    assert_eq!(protect(1, &Dna::from_slice(&s)), Dna::from_slice(&consumed));
+}
+
+pub fn check_pattern(data: &[u8]) {
+    let dna = bases(data);
+    let (p, consumed, rna) = decode(&dna, pattern::pattern);
+    assert!(rna.iter().all(|rna| rna.len() <= 7));
+    if let Ok(p) = p {
+        let mut depth = 0;
+        for item in &p {
+            match item {
+                PItem::Open => depth += 1,
+                PItem::Close => depth -= 1,
+                _ => {}
+            }
+            assert!(depth >= 0, "Close without Open in {:?}", p);
+        }
+        let mut encoded = pattern::encode(&p);
+        encoded.extend([Base::I, Base::I, Base::C]);
+        assert!(encoded.len() <= consumed.len());
+        // rna between a search and a base would glue them together when re-encoded
+        if rna.is_empty() {
+            let (decoded, reconsumed, _) = decode(&encoded, pattern::pattern);
+            assert_eq!(decoded, Ok(p));
+            assert_eq!(reconsumed, encoded);
+        }
+    }
+}
+
+pub fn check_template(data: &[u8]) {
+    let dna = bases(data);
+    let (t, consumed, _) = decode(&dna, template::template);
+    if let Ok(t) = t {
+        let mut encoded = template::encode(&t);
+        encoded.extend([Base::I, Base::I, Base::C]);
+        assert!(encoded.len() <= consumed.len());
+        let (decoded, reconsumed, _) = decode(&encoded, template::template);
+        assert_eq!(decoded, Ok(t));
+        assert_eq!(reconsumed, encoded);
+    }
+}
+
+// an arbitrary pattern, not necessarily one the decoder could produce
+fn arbitrary_pattern(data: &[u8]) -> Pattern {
+    let mut data = data.iter();
+    let mut p = Vec::new();
+    while let Some(op) = data.next() {
+        let arg = *data.next().unwrap_or(&0);
+        p.push(match op % 5 {
+            0 => PItem::PBase(bases(&[arg])[0]),
+            1 => PItem::Skip { n: arg as usize },
+            2 => PItem::Search { s: bases(&[arg, arg / 4, arg / 16])[..(arg % 4) as usize].to_vec() },
+            3 => PItem::Open,
+            _ => PItem::Close,
+        });
+    }
+    p
+}
+
+/// First half of the bytes is a pattern, the second one the dna to match.
+pub fn check_match(data: &[u8]) {
+    let (p, dna) = data.split_at(data.len() / 2);
+    let p = arbitrary_pattern(p);
+    let dna = bases(dna);
+    let closes = p.iter().filter(|item| **item == PItem::Close).count();
+    let mut context = Context::new(Dna::from_slice(&dna));
+    if let Some(env) = match_pat(&mut context, p) {
+        assert!(context.dna.len() <= dna.len());
+        assert_eq!(env.len(), closes);
+        // an empty template drops the matched prefix and nothing else
+        let rest = context.dna.len();
+        replace(&mut context, Vec::new(), &env);
+        assert_eq!(context.dna.len(), rest);
+    } else {
+        assert_eq!(context.dna.len(), dna.len());
+    }
+}
+
+// deep protection levels blow the dna up exponentially, which is not a bug
+fn too_expensive(dna: &Dna) -> bool {
+    let mut context = Context::new(dna.clone());
+    match pattern::pattern(&mut context).and_then(|_| template::template(&mut context)) {
+        Ok(t) => t.iter().any(|item| matches!(item, TItem::Ref { l, .. } if *l > 4)),
+        Err(_) => false,
+    }
+}
+
+/// A few whole steps, compared against the reference interpreter.
+pub fn check_step(data: &[u8]) {
+    let dna = bases(data);
+    let mut optimised = Context::new(Dna::from_slice(&dna));
+    let mut naive = reference::Context::new(dna);
+    for _ in 0..8 {
+        if too_expensive(&optimised.dna) {
+            return;
+        }
+        let expected = reference::do_step(&mut naive);
+        assert_eq!(interpreter::do_step(&mut optimised), expected);
+        assert_eq!(optimised.rna, naive.rna);
+        if expected.is_err() {
+            return;
+        }
+        assert_eq!(optimised.dna, Dna::from_slice(&naive.dna));
+    }
+}
+
+fn corpus_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| entries.map(|entry| entry.unwrap().path()).collect())
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+crate::entry_point!("fuzz-replay", fuzz_replay_main, "<target> [dir]",
+                    "Runs a fuzz target's check on every file of a corpus (default: fuzz/corpus/<target>)");
+fn fuzz_replay_main(args: &Args) -> Result<(), String> {
+    let target = args.arg("target");
+    let check = TARGETS.iter().find(|(name, _)| *name == target)
+        .map(|(_, check)| check)
+        .ok_or_else(|| format!("Unknown target {:?}, expected one of {:?}", target,
+                               TARGETS.iter().map(|(name, _)| *name).collect::<Vec<_>>()))?;
+    let dir = args.get("dir").map(PathBuf::from)
+        .unwrap_or_else(|| ["fuzz", "corpus", target].iter().collect());
+    let files = corpus_files(&dir);
+    for file in &files {
+        println!("{}", file.display());
+        check(&std::fs::read(file).map_err(|err| err.to_string())?);
+    }
+    println!("{} inputs passed", files.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift, to keep the generated inputs reproducible without extra dependencies
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        // mostly dna letters with runs of I, which start most of the interesting codes
+        fn input(&mut self) -> Vec<u8> {
+            let len = (self.next() % 64) as usize;
+            (0..len).map(|_| match self.next() % 6 {
+                0 | 1 => b'I',
+                2 => b'C',
+                3 => b'F',
+                4 => b'P',
+                _ => self.next() as u8,
+            }).collect()
+        }
+    }
+
+    #[test]
+    fn generated_inputs_test() {
+        for (name, check) in TARGETS {
+            let mut random = Random(0xf022);
+            for _ in 0..3000 {
+                let input = random.input();
+                let result = std::panic::catch_unwind(|| check(&input));
+                assert!(result.is_ok(), "{} failed on {:?}", name, String::from_utf8_lossy(&input));
+            }
+        }
+    }
+
+    #[test]
+    fn corpus_test() {
+        for (name, check) in TARGETS {
+            for file in corpus_files(&Path::new("fuzz").join("corpus").join(name)) {
+                check(&std::fs::read(file).unwrap());
+            }
+        }
+    }
+}
@@ -1,8 +1,12 @@
 use crate::image::DrawCommand;
-use crate::interpreter::dna::{Dna, ShortDna};
-use crate::interpreter::match_replace::replace;
+use crate::interpreter::dna::{Base, Dna, ShortDna};
+use crate::interpreter::literals::protect;
+use crate::interpreter::match_replace::{Environment, match_pat_with, replace_with};
+use crate::interpreter::pattern::Pattern;
+use crate::interpreter::step_cache::StepCache;
+use crate::interpreter::template::Template;
 
-use super::{match_replace::match_pat, pattern::pattern, template::template};
+use super::{pattern::pattern, template::template};
 
 pub type InterpreterResult<T> = Result<T, String>;
 
@@ -11,6 +15,9 @@ pub type InterpreterResult<T> = Result<T, String>;
 pub struct Context {
     pub dna: Dna,
     pub rna: Vec<ShortDna>,
+    // for every rna, the step which emitted it
+    pub rna_steps: Vec<usize>,
+    pub step: usize,
 }
 
 impl Context {
@@ -18,11 +25,14 @@ impl Context {
         Context {
             dna,
             rna: vec![],
+            rna_steps: vec![],
+            step: 0,
         }
     }
 
     pub fn append_rna(&mut self, rna: ShortDna) {
         self.rna.push(rna);
+        self.rna_steps.push(self.step);
     }
 
     pub fn draw_commands(&self) -> Vec<DrawCommand> {
@@ -30,20 +40,116 @@ impl Context {
             DrawCommand::decode(dna)
         }).collect()
     }
+
+    /// `draw_commands` together with the steps which emitted them, in the same order.
+    pub fn draw_commands_with_steps(&self) -> (Vec<DrawCommand>, Vec<usize>) {
+        self.rna.iter().zip(&self.rna_steps).filter_map(|(dna, step)| {
+            DrawCommand::decode(dna).map(|command| (command, *step))
+        }).unzip()
+    }
+}
+
+pub fn decode(context: &mut Context) -> InterpreterResult<(Pattern, Template)> {
+    Ok((pattern(context)?, template(context)?))
+}
+
+/// Hooks into the phases of `step_with`, by default a plain step of the spec.
+pub trait StepObserver {
+    /// Decodes the pattern then the template.
+    fn decode(&mut self, context: &mut Context) -> InterpreterResult<(Pattern, Template)> {
+        decode(context)
+    }
+
+    fn decoded(&mut self, _pattern: &Pattern, _template: &Template) {}
+
+    fn matching(&mut self) {}
+
+    /// Searches done by the match, see `Dna::find`.
+    fn find(&mut self, dna: &Dna, from: usize, target: &[Base]) -> Option<usize> {
+        dna.find(from, target)
+    }
+
+    /// Only called when the pattern matched, `context.dna` is what follows the match.
+    fn replacing(&mut self, _context: &Context) {}
+
+    /// Protection of references by the replacement.
+    fn protect(&mut self, l: usize, dna: &Dna) -> Dna {
+        protect(l, dna)
+    }
+
+    fn done(&mut self) {}
+}
+
+struct Plain;
+
+impl StepObserver for Plain {}
+
+/// The one implementation of a step, returns the environment if the pattern matched.
+pub fn step_with<O: StepObserver>(context: &mut Context, observer: &mut O) -> InterpreterResult<Option<Environment>> {
+    let (p, t) = observer.decode(context)?;
+    observer.decoded(&p, &t);
+    observer.matching();
+    let env = match_pat_with(context, p, |dna, from, s| observer.find(dna, from, s));
+    if let Some(env) = &env {
+        observer.replacing(context);
+        replace_with(context, t, env, |l, dna| observer.protect(l, dna));
+    }
+    context.step += 1;
+    observer.done();
+    Ok(env)
 }
 
 pub fn do_step(context: &mut Context) -> InterpreterResult<()> {
-    let p = pattern(context)?;
-    let t = template(context)?;
-    if let Some(env) = match_pat(context, p) {
-        replace(context, t, env);
+    step_with(context, &mut Plain).map(drop)
+}
+
+/// Everything decoded during one step, for debugging.
+#[derive(Debug)]
+pub struct Step {
+    pub pattern: Pattern,
+    pub template: Template,
+    pub env: Option<Environment>,
+    /// How many bases at the end of the dna the step left as they were.
+    pub kept: usize,
+}
+
+// keeps a copy of what was decoded and the length of the dna after the match
+#[derive(Default)]
+struct Tracer(Option<(Pattern, Template)>, usize);
+
+impl StepObserver for Tracer {
+    fn decode(&mut self, context: &mut Context) -> InterpreterResult<(Pattern, Template)> {
+        let decoded = decode(context)?;
+        self.1 = context.dna.len();
+        Ok(decoded)
     }
-    return Ok(());
+
+    fn decoded(&mut self, pattern: &Pattern, template: &Template) {
+        self.0 = Some((pattern.clone(), template.clone()));
+    }
+
+    fn replacing(&mut self, context: &Context) {
+        self.1 = context.dna.len();
+    }
+}
+
+/// Same as `do_step`, but keeps the decoded pattern, template and environment.
+pub fn do_step_traced(context: &mut Context) -> InterpreterResult<Step> {
+    let mut tracer = Tracer::default();
+    let env = step_with(context, &mut tracer)?;
+    let (pattern, template) = tracer.0.unwrap();
+    Ok(Step { pattern, template, env, kept: tracer.1 })
+}
+
+/// Same as `do_step`, with the pattern and template decoded through `cache`.
+pub fn do_step_cached(context: &mut Context, cache: &mut StepCache) -> InterpreterResult<()> {
+    step_with(context, cache).map(drop)
 }
 
 pub fn do_all_steps(context: &mut Context) -> String {
+    let mut cache = StepCache::new();
     loop {
-        if let Err(reason_to_stop) = do_step(context) {
+        if let Err(reason_to_stop) = do_step_cached(context, &mut cache) {
             return reason_to_stop
         }
     }
@@ -1,28 +1,27 @@
-use std::mem::swap;
 use crate::interpreter::interpreter::{Context, InterpreterResult};
 use crate::interpreter::dna::{Base, Dna, ShortDna};
 
-// recursion
-// is it possible to overflow usize?
 pub fn nat(context: &mut Context) -> InterpreterResult<usize> {
     use Base::*;
-    return match context.dna.prefix(1).as_slice() {
-        [P, ..] => {
-            context.dna.skip(1);
-            Ok(0)
-        }
-        [I, ..] | [F, ..] => {
-            context.dna.skip(1);
-            nat(context).map(|n| n * 2)
-        }
-        [C, ..] => {
-            context.dna.skip(1);
-            nat(context).map(|n| n * 2 + 1)
-        }
-        dna_tail => {
-            return Err(format!("Unexpected dna when nat decoding {:?}", dna_tail).to_string());
+    // bits come least significant first and are terminated by P
+    let mut bits = Vec::new();
+    loop {
+        match context.dna.prefix(1).as_slice() {
+            [P] => {
+                context.dna.skip(1);
+                break;
+            }
+            [I] | [F] => bits.push(0),
+            [C] => bits.push(1),
+            dna_tail => {
+                return Err(format!("Unexpected dna when nat decoding {:?}", dna_tail).to_string());
+            }
         }
+        context.dna.skip(1);
     }
+    bits.iter().rev()
+        .try_fold(0usize, |n, bit| n.checked_mul(2)?.checked_add(*bit))
+        .ok_or_else(|| "Nat does not fit in usize".to_string())
 }
 
  pub fn asnat(mut n: usize) -> ShortDna {
@@ -68,28 +67,14 @@ pub fn nat(context: &mut Context) -> InterpreterResult<usize> {
  }
 
  pub fn protect(l: usize, dna: &Dna) -> Dna {
-     if l == 0 { return dna.clone(); }
-
-     let mut result = dna.to_vec(0..dna.len());
-     let mut next_vec = Vec::with_capacity(dna.len() * 2);
+     let mut result = dna.clone();
      for _ in 0..l {
-         next_vec.clear();
-
-         use Base::*;
-         for b in &result {
-             match *b {
-                 I => next_vec.push(C),
-                 C => next_vec.push(F),
-                 F => next_vec.push(P),
-                 P => {
-                     next_vec.push(I);
-                     next_vec.push(C);
-                 },
-             }
+         if result.is_empty() {
+             break;
          }
-         swap(&mut result, &mut next_vec);
+         result = result.quote();
      }
-     return Dna::from_slice(&result);
+     return result;
  }
 
 #[test]
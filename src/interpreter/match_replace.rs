@@ -1,4 +1,3 @@
-use std::collections::VecDeque;
 use std::fmt::{Debug, Formatter};
 
 use crate::interpreter::interpreter::{Context};
@@ -15,6 +14,14 @@ impl Environment {
     fn get(&self, n: usize) -> Option<&Dna> {
         self.0.get(n)
     }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl Debug for Environment {
@@ -30,23 +37,15 @@ impl Debug for Environment {
     }
 }
 
-fn find_subseq(source: impl Iterator<Item = Base>, target: &[Base]) -> Option<usize> {
-    assert!(target.len() > 0);
-    let mut window = VecDeque::with_capacity(target.len());
-    for (idx, b) in source.enumerate() {
-        if window.len() >= target.len() {
-            window.pop_front();
-        }
-        window.push_back(b);
-        window.make_contiguous();
-        if window.as_slices().0 == target {
-            return Some(idx + 1);
-        }
-    }
-    return None;
+pub fn match_pat(context: &mut Context, pat: Pattern) -> Option<Environment> {
+    match_pat_with(context, pat, |dna, from, s| dna.find(from, s))
 }
 
-pub fn match_pat(context: &mut Context, pat: Pattern) -> Option<Environment> {
+/// `match_pat` with searches done by `find`, so that they can be observed.
+pub fn match_pat_with<F>(context: &mut Context, pat: Pattern, mut find: F) -> Option<Environment>
+where
+    F: FnMut(&Dna, usize, &[Base]) -> Option<usize>,
+{
     let mut i: usize = 0;
     let mut env = vec![];
     //c is reversed
@@ -61,13 +60,10 @@ pub fn match_pat(context: &mut Context, pat: Pattern) -> Option<Environment> {
                 }
             }
             PItem::Skip { n } => {
-                i += n;
-                if i > context.dna.len() {
-                    return None;
-                }
+                i = i.checked_add(n).filter(|i| *i <= context.dna.len())?;
             }
             PItem::Search { s } => {
-                if let Some(n) = find_subseq(context.dna.into_iter().skip(i).cloned(), s.as_slice()) {
+                if let Some(n) = find(&context.dna, i, &s) {
                     i += n;
                 } else {
                     return None;
@@ -77,11 +73,8 @@ pub fn match_pat(context: &mut Context, pat: Pattern) -> Option<Environment> {
                 c.push(i);
             }
             PItem::Close => {
-                env.push(
-                    context
-                        .dna
-                        .subseq(c.pop().expect("Unexpectedly empty stack")..i),
-                );
+                // a Close without an Open can't come from the decoder, the match just fails
+                env.push(context.dna.subseq(c.pop()?..i));
             }
         }
     }
@@ -89,7 +82,15 @@ pub fn match_pat(context: &mut Context, pat: Pattern) -> Option<Environment> {
     return Some(Environment(env));
 }
 
-pub fn replace(context: &mut Context, template: Template, env: Environment) {
+pub fn replace(context: &mut Context, template: Template, env: &Environment) {
+    replace_with(context, template, env, protect)
+}
+
+/// `replace` with references quoted by `protect`, so that it can be observed.
+pub fn replace_with<F>(context: &mut Context, template: Template, env: &Environment, mut protect: F)
+where
+    F: FnMut(usize, &Dna) -> Dna,
+{
     let mut r = Dna::empty();
     for t in template {
         match t {
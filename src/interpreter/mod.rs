@@ -1,11 +1,25 @@
 
-mod rope;
-mod interpreter;
+#[doc(hidden)]
+pub mod rope;
+#[doc(hidden)]
+pub mod packed;
+pub mod interpreter;
 pub mod dna;
 pub mod pattern;
 pub mod template;
 pub mod literals;
+#[doc(hidden)]
+pub mod step_cache;
 
-mod runner;
-mod match_replace;
+#[doc(hidden)]
+pub mod runner;
+mod debugger;
+#[doc(hidden)]
+pub mod profiler;
+pub mod match_replace;
+mod reference;
+#[doc(hidden)]
+pub mod fuzzing;
+#[cfg(test)]
+mod spec_tests;
 mod cmd;
@@ -0,0 +1,549 @@
+//! Rope of bases packed four to a byte, the storage of `Dna`.
+//! Bulk operations read and write whole words of up to 32 bases,
+//! the first base in the lowest bits.
+use std::ops::Range;
+
+use xi_rope::Interval;
+use xi_rope::tree::{Cursor, Leaf, Node, NodeInfo, TreeBuilder};
+use xi_rope::interval::IntervalBounds;
+
+use crate::interpreter::dna::Base;
+
+pub const MAX_LEAF: usize = 4096;
+pub const MIN_LEAF: usize = MAX_LEAF / 2 - 1;
+const WORD: usize = 32;
+const LEAF_BYTES: usize = MAX_LEAF / 4;
+// a word at any position spans at most 9 bytes
+const SLACK: usize = 9;
+
+fn mask(n: usize) -> u64 {
+    if n >= WORD { !0 } else { (1 << (2 * n)) - 1 }
+}
+
+// shifts out n bases, all of them for n = 32
+fn drop_bases(word: u64, n: usize) -> u64 {
+    if n >= WORD { 0 } else { word >> (2 * n) }
+}
+
+fn code(base: Base) -> u64 {
+    base as u64
+}
+
+fn base(code: u64) -> Base {
+    [Base::I, Base::C, Base::F, Base::P][(code & 3) as usize]
+}
+
+/// Packs up to 32 bases into a word.
+pub fn pack(bases: &[Base]) -> u64 {
+    bases.iter().rev().fold(0, |word, b| word << 2 | code(*b))
+}
+
+#[derive(Clone)]
+pub struct PackedLeaf {
+    used: usize,
+    // bits after `used` are always zero
+    data: [u8; LEAF_BYTES + SLACK],
+}
+
+impl Default for PackedLeaf {
+    fn default() -> Self {
+        PackedLeaf { used: 0, data: [0; LEAF_BYTES + SLACK] }
+    }
+}
+
+impl PackedLeaf {
+    pub fn get(&self, idx: usize) -> Base {
+        base((self.data[idx / 4] >> (2 * (idx % 4))) as u64)
+    }
+
+    /// `n` bases from `pos`, n is at most 32.
+    pub fn word(&self, pos: usize, n: usize) -> u64 {
+        let byte = pos / 4;
+        let mut bytes = [0u8; 16];
+        bytes[..SLACK].copy_from_slice(&self.data[byte..byte + SLACK]);
+        let word = (u128::from_le_bytes(bytes) >> (2 * (pos % 4))) as u64;
+        word & mask(n)
+    }
+
+    /// Appends `n` bases of `word`, n is at most 32.
+    pub fn push_word(&mut self, word: u64, n: usize) {
+        let byte = self.used / 4;
+        let bytes = (((word & mask(n)) as u128) << (2 * (self.used % 4))).to_le_bytes();
+        for (dst, src) in self.data[byte..byte + SLACK].iter_mut().zip(bytes) {
+            *dst |= src;
+        }
+        self.used += n;
+    }
+
+    fn extend_from(&mut self, other: &PackedLeaf, range: Range<usize>) {
+        let mut pos = range.start;
+        while pos < range.end {
+            let n = (range.end - pos).min(WORD);
+            self.push_word(other.word(pos, n), n);
+            pos += n;
+        }
+    }
+
+    fn truncate(&mut self, len: usize) {
+        let byte = len / 4;
+        let rest = len % 4;
+        if rest > 0 {
+            self.data[byte] &= (1 << (2 * rest)) - 1;
+            self.data[byte + 1..].iter_mut().for_each(|b| *b = 0);
+        } else {
+            self.data[byte..].iter_mut().for_each(|b| *b = 0);
+        }
+        self.used = len;
+    }
+}
+
+impl Leaf for PackedLeaf {
+    fn len(&self) -> usize {
+        self.used
+    }
+
+    fn is_ok_child(&self) -> bool {
+        self.used >= MIN_LEAF
+    }
+
+    fn push_maybe_split(&mut self, other: &Self, iv: Interval) -> Option<Self> {
+        let (start, end) = iv.start_end();
+        if self.used + iv.size() <= MAX_LEAF {
+            self.extend_from(other, start..end);
+            None
+        } else {
+            let new_left_leaf_size = MAX_LEAF.min(self.used + iv.size() - MIN_LEAF);
+            let mut right_leaf = PackedLeaf::default();
+            if new_left_leaf_size > self.used {
+                let splitpoint = start + new_left_leaf_size - self.used;
+                self.extend_from(other, start..splitpoint);
+                right_leaf.extend_from(other, splitpoint..end);
+            } else {
+                right_leaf.extend_from(self, new_left_leaf_size..self.used);
+                right_leaf.extend_from(other, start..end);
+                self.truncate(new_left_leaf_size);
+            }
+            Some(right_leaf)
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct PackedInfo;
+
+impl NodeInfo for PackedInfo {
+    type L = PackedLeaf;
+
+    fn accumulate(&mut self, _other: &Self) {
+    }
+
+    fn compute_info(_leaf: &Self::L) -> Self {
+        PackedInfo
+    }
+}
+
+#[derive(Clone)]
+pub struct PackedSeq(Node<PackedInfo>);
+
+impl PackedSeq {
+    pub fn from_slice(bases: &[Base]) -> Self {
+        let mut builder = PackedBuilder::new();
+        for chunk in bases.chunks(WORD) {
+            builder.push_word(pack(chunk), chunk.len());
+        }
+        builder.build()
+    }
+
+    pub fn nth(&self, offset: usize) -> Option<Base> {
+        let cursor = Cursor::new(&self.0, offset);
+        let (leaf, leaf_offset) = cursor.get_leaf()?;
+        if leaf_offset < leaf.len() {
+            Some(leaf.get(leaf_offset))
+        } else {
+            None
+        }
+    }
+
+    pub fn subseq<I: IntervalBounds>(&self, iv: I) -> Self {
+        PackedSeq(self.0.subseq(iv))
+    }
+
+    pub fn concat(&self, other: &Self) -> Self {
+        PackedSeq(Node::concat(self.0.clone(), other.0.clone()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Words of the bases in `range`, clamped to the sequence.
+    pub fn words(&self, range: Range<usize>) -> Words<'_> {
+        let start = range.start.min(self.len());
+        let cursor = Cursor::new(&self.0, start);
+        Words {
+            leaf: cursor.get_leaf(),
+            cursor,
+            remaining: range.end.min(self.len()).saturating_sub(start),
+        }
+    }
+
+    /// Up to 32 bases from `start` in one word, whatever the leaf boundaries, and their count.
+    pub fn word(&self, start: usize) -> (u64, usize) {
+        self.words(start..start + WORD).fold((0, 0), |(word, len), (w, n)| (word | w << (2 * len), len + n))
+    }
+
+    pub fn bases(&self, range: Range<usize>) -> Bases<'_> {
+        Bases { words: self.words(range), word: 0, left: 0 }
+    }
+}
+
+/// Runs of up to 32 bases, never crossing a leaf boundary.
+pub struct Words<'a> {
+    cursor: Cursor<'a, PackedInfo>,
+    leaf: Option<(&'a PackedLeaf, usize)>,
+    remaining: usize,
+}
+
+impl Iterator for Words<'_> {
+    type Item = (u64, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining > 0 {
+            let (leaf, offset) = self.leaf?;
+            if offset < leaf.len() {
+                let n = (leaf.len() - offset).min(WORD).min(self.remaining);
+                self.leaf = Some((leaf, offset + n));
+                self.remaining -= n;
+                return Some((leaf.word(offset, n), n));
+            }
+            self.leaf = self.cursor.next_leaf();
+        }
+        None
+    }
+}
+
+pub struct Bases<'a> {
+    words: Words<'a>,
+    word: u64,
+    left: usize,
+}
+
+impl Iterator for Bases<'_> {
+    type Item = Base;
+
+    fn next(&mut self) -> Option<Base> {
+        if self.left == 0 {
+            let (word, n) = self.words.next()?;
+            self.word = word;
+            self.left = n;
+        }
+        let b = base(self.word);
+        self.word >>= 2;
+        self.left -= 1;
+        Some(b)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.left + self.words.remaining;
+        (remaining, Some(remaining))
+    }
+}
+
+/// Collects words into full leaves of a new sequence.
+pub struct PackedBuilder {
+    builder: TreeBuilder<PackedInfo>,
+    leaf: PackedLeaf,
+    // fewer than 32 bases not yet in the leaf
+    pending: u64,
+    pending_len: usize,
+}
+
+impl Default for PackedBuilder {
+    fn default() -> Self {
+        PackedBuilder::new()
+    }
+}
+
+impl PackedBuilder {
+    pub fn new() -> Self {
+        PackedBuilder { builder: TreeBuilder::new(), leaf: PackedLeaf::default(), pending: 0, pending_len: 0 }
+    }
+
+    pub fn push_word(&mut self, word: u64, n: usize) {
+        let word = word & mask(n);
+        self.pending |= word << (2 * self.pending_len);
+        let len = self.pending_len + n;
+        if len < WORD {
+            self.pending_len = len;
+            return;
+        }
+        // leaves are filled by whole words, so a full word always fits
+        self.leaf.push_word(self.pending, WORD);
+        if self.leaf.used == MAX_LEAF {
+            self.builder.push_leaf(std::mem::take(&mut self.leaf));
+        }
+        self.pending = drop_bases(word, WORD - self.pending_len);
+        self.pending_len = len - WORD;
+    }
+
+    pub fn build(mut self) -> PackedSeq {
+        self.leaf.push_word(self.pending, self.pending_len);
+        if self.leaf.used > 0 {
+            self.builder.push_leaf(self.leaf);
+        }
+        PackedSeq(self.builder.build())
+    }
+}
+
+/// Compares two runs of words of the same total length, whatever their chunking.
+pub fn same_bases<A, B>(mut a: A, mut b: B) -> bool
+where
+    A: Iterator<Item = (u64, usize)>,
+    B: Iterator<Item = (u64, usize)>,
+{
+    let (mut word_a, mut len_a) = (0, 0);
+    let (mut word_b, mut len_b) = (0, 0);
+    loop {
+        if len_a == 0 {
+            match a.next() {
+                Some((word, n)) => { word_a = word; len_a = n; }
+                None => return len_b == 0 && b.next().is_none(),
+            }
+        }
+        if len_b == 0 {
+            match b.next() {
+                Some((word, n)) => { word_b = word; len_b = n; }
+                None => return false,
+            }
+        }
+        let n = len_a.min(len_b);
+        if (word_a ^ word_b) & mask(n) != 0 {
+            return false;
+        }
+        word_a = drop_bases(word_a, n);
+        word_b = drop_bases(word_b, n);
+        len_a -= n;
+        len_b -= n;
+    }
+}
+
+/// Words of a slice of bases.
+pub fn slice_words(bases: &[Base]) -> impl Iterator<Item = (u64, usize)> + '_ {
+    bases.chunks(WORD).map(|chunk| (pack(chunk), chunk.len()))
+}
+
+/// Index right after the first occurrence of `target` in `seq[from..]`, relative to `from`.
+/// Takes 32 start positions at a time: the ones where the first base of the target is are
+/// found with a few operations on a whole word, at each of them the head of the target
+/// (up to 32 bases) is compared as one word, then the rest of it word by word.
+pub fn find(seq: &PackedSeq, from: usize, target: &[Base]) -> Option<usize> {
+    if target.is_empty() {
+        return Some(0);
+    }
+    let head = target.len().min(WORD);
+    let pattern = pack(&target[..head]);
+    // the first base of the target at every position of a word
+    let first = code(target[0]) * 0x5555_5555_5555_5555;
+    let mut words = seq.words(from..seq.len());
+    let mut exhausted = false;
+    // `buffered` bases from `start`, enough for the head at each of the next 32 positions
+    let (mut buffer, mut buffered, mut start) = (0u128, 0, from);
+    loop {
+        while buffered <= WORD && !exhausted {
+            match words.next() {
+                Some((word, n)) => {
+                    buffer |= (word as u128) << (2 * buffered);
+                    buffered += n;
+                }
+                None => exhausted = true,
+            }
+        }
+        if buffered < head {
+            return None;
+        }
+        let positions = (buffered + 1 - head).min(WORD);
+        // both bits of a base are zero where it is the same as the first one
+        let different = buffer as u64 ^ first;
+        let mut candidates = !(different | different >> 1) & 0x5555_5555_5555_5555 & mask(positions);
+        while candidates != 0 {
+            let i = candidates.trailing_zeros() as usize / 2;
+            candidates &= candidates - 1;
+            if (buffer >> (2 * i)) as u64 & mask(head) == pattern {
+                let end = start + i + target.len();
+                let tail = seq.words(start + i + head..end);
+                if end <= seq.len() && same_bases(tail, slice_words(&target[head..])) {
+                    return Some(end - from);
+                }
+            }
+        }
+        buffer >>= 2 * positions;
+        buffered -= positions;
+        start += positions;
+    }
+}
+
+// quote of every byte of four bases: the quoted word and its length
+static QUOTED: [(u16, u8); 256] = quote_table();
+
+const fn quote_table() -> [(u16, u8); 256] {
+    let mut table = [(0u16, 0u8); 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let (mut word, mut len, mut i) = (0u16, 0u8, 0);
+        while i < 4 {
+            let b = (byte >> (2 * i)) & 3;
+            if b == 3 {
+                // P becomes IC
+                word |= 1 << (2 * (len + 1));
+                len += 2;
+            } else {
+                word |= ((b + 1) as u16) << (2 * len);
+                len += 1;
+            }
+            i += 1;
+        }
+        table[byte] = (word, len);
+        byte += 1;
+    }
+    table
+}
+
+/// Quotes every base of `range`, a byte of four bases at a time.
+pub fn quote(seq: &PackedSeq, range: Range<usize>) -> PackedSeq {
+    let mut builder = PackedBuilder::new();
+    for (word, n) in seq.words(range) {
+        let bytes = n / 4;
+        for i in 0..bytes {
+            let (quoted, len) = QUOTED[(word >> (8 * i) & 0xff) as usize];
+            builder.push_word(quoted as u64, len as usize);
+        }
+        for i in 4 * bytes..n {
+            let (quoted, len) = QUOTED[(word >> (2 * i) & 3) as usize];
+            // a lone base reads as a byte ending with three Is, their three quoted Cs are dropped
+            builder.push_word(quoted as u64, len as usize - 3);
+        }
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Base::*;
+
+    fn random_bases(len: usize, seed: u64) -> Vec<Base> {
+        let mut state = seed;
+        (0..len).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            base(state)
+        }).collect()
+    }
+
+    fn to_vec(seq: &PackedSeq) -> Vec<Base> {
+        seq.bases(0..seq.len()).collect()
+    }
+
+    #[test]
+    fn packing_test() {
+        for len in [0, 1, 5, 31, 32, 33, MAX_LEAF - 1, MAX_LEAF, 3 * MAX_LEAF + 17] {
+            let bases = random_bases(len, len as u64 + 1);
+            let seq = PackedSeq::from_slice(&bases);
+            assert_eq!(seq.len(), len);
+            assert_eq!(to_vec(&seq), bases);
+            assert_eq!(seq.nth(len / 2), bases.get(len / 2).cloned());
+            assert_eq!(seq.bases(len / 3..len / 2).size_hint().0, len / 2 - len / 3);
+        }
+    }
+
+    #[test]
+    fn subseq_and_concat_test() {
+        let bases = random_bases(5 * MAX_LEAF + 3, 7);
+        let seq = PackedSeq::from_slice(&bases);
+        // unaligned cuts go through push_maybe_split at every bit offset
+        for (start, end) in [(1, 2), (3, 4100), (4097, 9000), (1001, bases.len()), (MAX_LEAF - 3, MAX_LEAF + 5)] {
+            let sub = seq.subseq(start..end);
+            assert_eq!(to_vec(&sub), &bases[start..end]);
+            let joined = sub.concat(&seq.subseq(end..));
+            assert_eq!(to_vec(&joined), &bases[start..]);
+            let at = (end - start).saturating_sub(3);
+            let across = &bases[start + at..(start + at + 32).min(bases.len())];
+            assert_eq!(joined.word(at), (pack(across), across.len()));
+        }
+        let mut many = PackedSeq::from_slice(&[]);
+        for i in 0..300 {
+            many = many.concat(&seq.subseq(i..i + 7));
+        }
+        let expected: Vec<Base> = (0..300).flat_map(|i| bases[i..i + 7].to_vec()).collect();
+        assert_eq!(to_vec(&many), expected);
+        // words of a few bases each, merged
+        assert_eq!(many.word(5), (pack(&expected[5..37]), 32));
+        assert_eq!(many.word(2090), (pack(&expected[2090..]), 10));
+    }
+
+    #[test]
+    fn same_bases_test() {
+        let bases = random_bases(2000, 3);
+        let seq = PackedSeq::from_slice(&bases);
+        let other = seq.subseq(..77).concat(&seq.subseq(77..));
+        assert!(same_bases(seq.words(0..2000), other.words(0..2000)));
+        assert!(same_bases(seq.words(10..1500), slice_words(&bases[10..1500])));
+        assert!(!same_bases(seq.words(10..1500), slice_words(&bases[11..1501])));
+        assert!(!same_bases(seq.words(0..10), slice_words(&bases[..9])));
+    }
+
+    #[test]
+    fn find_test() {
+        let seq = PackedSeq::from_slice(&[I, C, F, P, I, C, C]);
+        assert_eq!(find(&seq, 0, &[]), Some(0));
+        assert_eq!(find(&seq, 0, &[C, F]), Some(3));
+        assert_eq!(find(&seq, 1, &[I, C]), Some(5));
+        assert_eq!(find(&seq, 5, &[I]), None);
+        assert_eq!(find(&seq, 0, &[C, C, C]), None);
+        // targets longer than a word are checked past the head
+        let bases = random_bases(3 * MAX_LEAF, 11);
+        let seq = PackedSeq::from_slice(&bases);
+        for (start, len) in [(4000, 40), (100, 33), (MAX_LEAF - 20, 100), (3 * MAX_LEAF - 50, 50)] {
+            let target = &bases[start..start + len];
+            let first = bases.windows(len).position(|w| w == target).unwrap();
+            assert_eq!(find(&seq, 0, target), Some(first + len));
+        }
+        assert_eq!(find(&seq, 0, &bases[10..50].iter().cloned().chain([P; 3]).collect::<Vec<_>>()), None);
+        // short targets match at every offset of a word, and the zero padding past the end isn't a run of I
+        let bases: Vec<Base> = random_bases(MAX_LEAF + 70, 13).into_iter().chain([I; 5]).collect();
+        let seq = PackedSeq::from_slice(&bases);
+        let naive = |from: usize, target: &[Base]| {
+            bases[from..].windows(target.len()).position(|w| w == target).map(|at| at + target.len())
+        };
+        for from in [0, 1, 31, 32, 33, MAX_LEAF - 3, MAX_LEAF + 40] {
+            for len in [1, 2, 3, 7, 31, 32, 33] {
+                for start in [from, (from + 5).min(bases.len() - len), bases.len() - len] {
+                    let target = &bases[start..start + len];
+                    assert_eq!(find(&seq, from, target), naive(from, target), "from {} len {}", from, len);
+                }
+            }
+            for len in [5, 6, 20] {
+                assert_eq!(find(&seq, from, &vec![I; len]), naive(from, &vec![I; len]), "from {} {} I", from, len);
+            }
+        }
+    }
+
+    #[test]
+    fn quote_test() {
+        let seq = PackedSeq::from_slice(&[I, C, F, P, P, I]);
+        assert_eq!(to_vec(&quote(&seq, 0..6)), vec![C, F, P, I, C, I, C, C]);
+        let bases = random_bases(3000, 5);
+        let expected: Vec<Base> = bases[3..].iter().flat_map(|b| match b {
+            I => vec![C],
+            C => vec![F],
+            F => vec![P],
+            P => vec![I, C],
+        }).collect();
+        assert_eq!(to_vec(&quote(&PackedSeq::from_slice(&bases), 3..3000)), expected);
+    }
+}
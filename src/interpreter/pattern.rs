@@ -4,7 +4,7 @@ use crate::interpreter::interpreter::{Context, InterpreterResult};
 use crate::interpreter::literals::*;
 use crate::interpreter::pattern::PItem::PBase;
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Eq, PartialEq, Hash)]
 pub enum PItem {
     PBase(Base),
     Skip { n: usize },
@@ -62,7 +62,7 @@ impl PItem {
                 result.extend([I, I, P]);
             }
             PItem::Close => {
-                result.extend([I, I, P]);
+                result.extend([I, I, C]);
             }
         }
         return result;
@@ -0,0 +1,397 @@
+//! Opt-in profile of interpreter steps: time and allocations by phase and by pattern.
+//! Allocations are counted where `CountingAllocator` is installed: in the binary built
+//! with the `count-allocations` feature, and in the lib tests.
+//! They are counted per thread, a profiler only sees those of its own thread.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{AddAssign, Sub};
+use std::time::{Duration, Instant};
+
+use crate::interpreter::dna::{Base, Dna};
+use crate::interpreter::interpreter::{step_with, Context, InterpreterResult, StepObserver};
+use crate::interpreter::literals::protect;
+use crate::interpreter::pattern::{pattern, Pattern};
+use crate::interpreter::step_cache::StepCache;
+use crate::interpreter::template::{template, Template};
+
+#[derive(Clone, Copy)]
+struct Counter {
+    // profilers alive on the thread, allocations are counted while there is any
+    profilers: usize,
+    allocations: u64,
+    bytes: u64,
+}
+
+thread_local! {
+    // const and without destructor, so the allocator can use it without allocating
+    static COUNTER: Cell<Counter> = const { Cell::new(Counter { profilers: 0, allocations: 0, bytes: 0 }) };
+}
+
+fn counter() -> Counter {
+    COUNTER.with(Cell::get)
+}
+
+/// System allocator which counts allocations while a `Profiler` exists.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[cfg(test)]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count(bytes: usize) {
+    // the thread may be past its thread locals, then there is no profiler on it either
+    let _ = COUNTER.try_with(|counter| {
+        let mut current = counter.get();
+        if current.profilers > 0 {
+            current.allocations += 1;
+            current.bytes += bytes as u64;
+            counter.set(current);
+        }
+    });
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Cost {
+    time: Duration,
+    allocations: u64,
+    bytes: u64,
+}
+
+impl AddAssign for Cost {
+    fn add_assign(&mut self, other: Cost) {
+        self.time += other.time;
+        self.allocations += other.allocations;
+        self.bytes += other.bytes;
+    }
+}
+
+impl Sub for Cost {
+    type Output = Cost;
+
+    fn sub(self, other: Cost) -> Cost {
+        Cost {
+            time: self.time.saturating_sub(other.time),
+            allocations: self.allocations.saturating_sub(other.allocations),
+            bytes: self.bytes.saturating_sub(other.bytes),
+        }
+    }
+}
+
+struct Mark {
+    at: Instant,
+    allocations: u64,
+    bytes: u64,
+}
+
+impl Mark {
+    fn now() -> Self {
+        let counter = counter();
+        Mark { at: Instant::now(), allocations: counter.allocations, bytes: counter.bytes }
+    }
+
+    fn cost(&self) -> Cost {
+        let counter = counter();
+        Cost {
+            time: self.at.elapsed(),
+            allocations: counter.allocations - self.allocations,
+            bytes: counter.bytes - self.bytes,
+        }
+    }
+}
+
+// decodes which miss the cache are measured on their own and not counted in the lookup
+const PHASES: [&str; 7] = ["cache lookup", "pattern decode", "template decode", "match", "search", "replace", "protect"];
+const LOOKUP: usize = 0;
+const PATTERN: usize = 1;
+const TEMPLATE: usize = 2;
+const MATCH: usize = 3;
+const SEARCH: usize = 4;
+const REPLACE: usize = 5;
+const PROTECT: usize = 6;
+
+struct PatternCost {
+    // the pattern as shown in the report, cut to LABEL_LEN
+    label: String,
+    steps: usize,
+    // steps decoded from the cache
+    hits: usize,
+    cost: Cost,
+}
+
+const LABEL_LEN: usize = 60;
+
+fn label(pattern: &Pattern) -> String {
+    if pattern.is_empty() {
+        return "(empty)".to_string();
+    }
+    let label = pattern.iter().map(|item| format!("{:?}", item)).collect::<Vec<_>>().join(" ");
+    match label.char_indices().nth(LABEL_LEN) {
+        Some((end, _)) => format!("{}...", &label[..end]),
+        None => label,
+    }
+}
+
+// measures the phases of one step
+struct Phases<'a> {
+    cache: &'a mut StepCache,
+    patterns: &'a mut HashMap<u64, PatternCost>,
+    costs: [Cost; 7],
+    fingerprint: u64,
+    // start of the match, then of the replacement
+    mark: Mark,
+    replacing: bool,
+}
+
+impl StepObserver for Phases<'_> {
+    fn decode(&mut self, context: &mut Context) -> InterpreterResult<(Pattern, Template)> {
+        let mark = Mark::now();
+        let costs = &mut self.costs;
+        let decoded = self.cache.decode_with(context, |context| {
+            let mark = Mark::now();
+            let p = pattern(context);
+            costs[PATTERN] = mark.cost();
+            let p = p?;
+            let mark = Mark::now();
+            let t = template(context);
+            costs[TEMPLATE] = mark.cost();
+            Ok((p, t?))
+        });
+        self.costs[LOOKUP] = mark.cost() - self.costs[PATTERN] - self.costs[TEMPLATE];
+        decoded
+    }
+
+    fn decoded(&mut self, pattern: &Pattern, _template: &Template) {
+        let mut hasher = DefaultHasher::new();
+        pattern.hash(&mut hasher);
+        self.fingerprint = hasher.finish();
+        self.patterns.entry(self.fingerprint)
+            .or_insert_with(|| PatternCost { label: label(pattern), steps: 0, hits: 0, cost: Cost::default() });
+    }
+
+    fn matching(&mut self) {
+        self.mark = Mark::now();
+    }
+
+    fn find(&mut self, dna: &Dna, from: usize, target: &[Base]) -> Option<usize> {
+        let mark = Mark::now();
+        let found = dna.find(from, target);
+        self.costs[SEARCH] += mark.cost();
+        found
+    }
+
+    fn replacing(&mut self, _context: &Context) {
+        self.costs[MATCH] = self.mark.cost() - self.costs[SEARCH];
+        self.mark = Mark::now();
+        self.replacing = true;
+    }
+
+    fn protect(&mut self, l: usize, dna: &Dna) -> Dna {
+        let mark = Mark::now();
+        let result = protect(l, dna);
+        self.costs[PROTECT] += mark.cost();
+        result
+    }
+
+    fn done(&mut self) {
+        if self.replacing {
+            self.costs[REPLACE] = self.mark.cost() - self.costs[PROTECT];
+        } else {
+            self.costs[MATCH] = self.mark.cost() - self.costs[SEARCH];
+        }
+    }
+}
+
+/// Does steps like `do_step_cached`, measuring each phase.
+/// Search and protect are measured on their own and not counted in match and replace.
+pub struct Profiler {
+    steps: usize,
+    phases: [Cost; 7],
+    patterns: HashMap<u64, PatternCost>,
+    hits: usize,
+    misses: usize,
+    // allocations are counted on the thread which created the profiler, so it stays there
+    not_send: PhantomData<*const ()>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        update_profilers(|profilers| profilers + 1);
+        Profiler {
+            steps: 0,
+            phases: [Cost::default(); 7],
+            patterns: HashMap::new(),
+            hits: 0,
+            misses: 0,
+            not_send: PhantomData,
+        }
+    }
+
+    pub fn step(&mut self, context: &mut Context, cache: &mut StepCache) -> InterpreterResult<()> {
+        let hits = cache.hits;
+        let misses = cache.misses;
+        let mut phases = Phases {
+            cache,
+            patterns: &mut self.patterns,
+            costs: [Cost::default(); 7],
+            fingerprint: 0,
+            mark: Mark::now(),
+            replacing: false,
+        };
+        let result = step_with(context, &mut phases);
+        let (costs, fingerprint) = (phases.costs, phases.fingerprint);
+        let hit = cache.hits - hits;
+        self.hits += hit;
+        self.misses += cache.misses - misses;
+        for (total, cost) in self.phases.iter_mut().zip(costs) {
+            *total += cost;
+        }
+        result?;
+        let pattern = self.patterns.get_mut(&fingerprint).unwrap();
+        pattern.steps += 1;
+        pattern.hits += hit;
+        for cost in costs {
+            pattern.cost += cost;
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    /// Totals by phase and the `top` most expensive patterns.
+    pub fn report(&self, top: usize) -> String {
+        let mut total = Cost::default();
+        for cost in self.phases {
+            total += cost;
+        }
+        let share = |cost: &Cost| 100.0 * cost.time.as_secs_f64() / total.time.as_secs_f64().max(1e-9);
+        let mut report = format!("Profile of {} steps, {:?} in the interpreter\n", self.steps, total.time);
+        if !cfg!(any(test, feature = "count-allocations")) {
+            report.push_str("Allocations are not counted, build with --features count-allocations\n");
+        }
+        report.push_str(&format!("Step cache: {} hits, {} misses\n", self.hits, self.misses));
+        report.push_str(&format!("{:<16} {:>12} {:>6} {:>12} {:>12}\n", "phase", "time", "%", "allocs", "bytes"));
+        for (name, cost) in PHASES.iter().zip(&self.phases) {
+            report.push_str(&format!("{:<16} {:>12.3?} {:>6.1} {:>12} {:>12}\n",
+                                     name, cost.time, share(cost), cost.allocations, cost.bytes));
+        }
+        let mut patterns: Vec<&PatternCost> = self.patterns.values().collect();
+        patterns.sort_by_key(|pattern| Reverse(pattern.cost.time));
+        report.push_str(&format!("\nTop {} of {} patterns by time:\n", top.min(patterns.len()), patterns.len()));
+        report.push_str(&format!("{:>10} {:>10} {:>12} {:>6} {:>12}  {}\n", "steps", "cached", "time", "%", "allocs", "pattern"));
+        for pattern in patterns.iter().take(top) {
+            report.push_str(&format!("{:>10} {:>10} {:>12.3?} {:>6.1} {:>12}  {}\n",
+                                     pattern.steps, pattern.hits, pattern.cost.time, share(&pattern.cost),
+                                     pattern.cost.allocations, pattern.label));
+        }
+        report
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        update_profilers(|profilers| profilers - 1);
+    }
+}
+
+fn update_profilers(update: impl FnOnce(usize) -> usize) {
+    COUNTER.with(|counter| {
+        let current = counter.get();
+        counter.set(Counter { profilers: update(current.profilers), ..current });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::dna::Dna;
+    use crate::interpreter::interpreter::do_step;
+
+    #[test]
+    fn profiled_steps_test() {
+        use crate::interpreter::dna::Base::*;
+        use crate::interpreter::pattern::{encode, PItem};
+        use crate::interpreter::template::{self, TItem};
+
+        let end = [I, I, C];
+        // (PPPP) -> the same group, twice, then I which doesn't match, then not enough dna
+        let search = [encode(&vec![PItem::Open, PItem::Search { s: vec![P; 4] }, PItem::Close]), end.to_vec(),
+                      template::encode(&vec![TItem::Ref { n: 0, l: 0 }]), end.to_vec()].concat();
+        let base = [encode(&vec![PItem::PBase(I)]), end.to_vec(), end.to_vec()].concat();
+        let dna = [search.clone(), search, base, vec![P; 4]].concat();
+        let mut profiled = Context::new(Dna::from_slice(&dna));
+        let mut plain = Context::new(Dna::from_slice(&dna));
+        let mut profiler = Profiler::new();
+        let mut cache = StepCache::new();
+        for _ in 0..4 {
+            assert_eq!(profiler.step(&mut profiled, &mut cache), do_step(&mut plain));
+            assert_eq!(profiled.dna, plain.dna);
+        }
+        assert_eq!(profiled.step, plain.step);
+        assert_eq!(profiler.steps, 3);
+        assert_eq!(profiler.patterns.len(), 2);
+        // the failed decode is a miss too
+        assert_eq!((profiler.hits, profiler.misses), (cache.hits, cache.misses));
+        assert_eq!(profiler.hits + profiler.misses, 4);
+        // decoding a pattern allocates its items
+        assert!(profiler.phases[PATTERN].allocations >= 3);
+        assert!(profiler.phases[PATTERN].bytes > 0);
+        // only this thread's allocations are counted, whatever the other tests do meanwhile
+        let mark = Mark::now();
+        let words: Vec<u64> = Vec::with_capacity(16);
+        let cost = mark.cost();
+        assert_eq!((cost.allocations, cost.bytes), (1, 128));
+        drop(words);
+        let report = profiler.report(1);
+        assert!(report.contains("Profile of 3 steps"));
+        assert!(report.contains("Top 1 of 2 patterns"));
+    }
+
+    #[test]
+    fn live_profilers_test() {
+        let first = Profiler::new();
+        let second = Profiler::new();
+        drop(first);
+        let before = counter().allocations;
+        let allocated = vec![0u8; 100];
+        assert_eq!(counter().allocations, before + 1);
+        drop(second);
+        // nothing is counted without a profiler
+        let again = vec![0u8; 100];
+        assert_eq!(counter().allocations, before + 1);
+        drop((allocated, again));
+        // another thread starts with its own counts
+        std::thread::spawn(|| {
+            let _profiler = Profiler::new();
+            assert_eq!(counter().allocations, 0);
+            let allocated = vec![0u8; 100];
+            assert_eq!((counter().allocations, counter().bytes), (1, 100));
+            drop(allocated);
+        }).join().unwrap();
+    }
+}
@@ -0,0 +1,346 @@
+//! Deliberately naive interpreter over a plain `Vec<Base>`, transcribed from the spec
+//! without any of the optimisations of `interpreter::interpreter`.
+//! It is slow and only meant as an oracle for differential tests.
+use crate::interpreter::dna::{Base, ShortDna};
+use crate::interpreter::interpreter::InterpreterResult;
+use crate::interpreter::pattern::{Pattern, PItem};
+use crate::interpreter::template::{Template, TItem};
+
+use Base::*;
+
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub dna: Vec<Base>,
+    pub rna: Vec<ShortDna>,
+    pub step: usize,
+}
+
+impl Context {
+    pub fn new(dna: Vec<Base>) -> Self {
+        Context { dna, rna: vec![], step: 0 }
+    }
+}
+
+// up to three bases at `i`, as shown in the error messages of the optimised interpreter
+fn tail(dna: &[Base], i: usize, len: usize) -> Vec<Base> {
+    dna[i.min(dna.len())..(i + len).min(dna.len())].to_vec()
+}
+
+fn nat(dna: &[Base], i: &mut usize) -> InterpreterResult<usize> {
+    let mut bits = Vec::new();
+    loop {
+        match dna.get(*i) {
+            Some(P) => {
+                *i += 1;
+                break;
+            }
+            Some(I) | Some(F) => bits.push(0),
+            Some(C) => bits.push(1),
+            None => return Err(format!("Unexpected dna when nat decoding {:?}", tail(dna, *i, 1))),
+        }
+        *i += 1;
+    }
+    bits.iter().rev()
+        .try_fold(0usize, |n, bit| n.checked_mul(2)?.checked_add(*bit))
+        .ok_or_else(|| "Nat does not fit in usize".to_string())
+}
+
+fn consts(dna: &[Base], i: &mut usize) -> ShortDna {
+    let mut s = Vec::new();
+    loop {
+        match (dna.get(*i), dna.get(*i + 1)) {
+            (Some(C), _) => s.push(I),
+            (Some(F), _) => s.push(C),
+            (Some(P), _) => s.push(F),
+            (Some(I), Some(C)) => {
+                *i += 1;
+                s.push(P);
+            }
+            _ => return s,
+        }
+        *i += 1;
+    }
+}
+
+fn emit_rna(dna: &[Base], i: &mut usize, rna: &mut Vec<ShortDna>) {
+    rna.push(dna[(*i + 3).min(dna.len())..(*i + 10).min(dna.len())].to_vec());
+    *i = (*i + 10).min(dna.len());
+}
+
+fn pattern(dna: &[Base], i: &mut usize, rna: &mut Vec<ShortDna>) -> InterpreterResult<Pattern> {
+    let mut p = Vec::new();
+    let mut lvl = 0;
+    loop {
+        match (dna.get(*i), dna.get(*i + 1), dna.get(*i + 2)) {
+            (Some(C), _, _) => { *i += 1; p.push(PItem::PBase(I)); }
+            (Some(F), _, _) => { *i += 1; p.push(PItem::PBase(C)); }
+            (Some(P), _, _) => { *i += 1; p.push(PItem::PBase(F)); }
+            (Some(I), Some(C), _) => { *i += 2; p.push(PItem::PBase(P)); }
+            (Some(I), Some(P), _) => {
+                *i += 2;
+                p.push(PItem::Skip { n: nat(dna, i)? });
+            }
+            (Some(I), Some(F), _) => {
+                *i = (*i + 3).min(dna.len());
+                p.push(PItem::Search { s: consts(dna, i) });
+            }
+            (Some(I), Some(I), Some(P)) => {
+                *i += 3;
+                lvl += 1;
+                p.push(PItem::Open);
+            }
+            (Some(I), Some(I), Some(C)) | (Some(I), Some(I), Some(F)) => {
+                *i += 3;
+                if lvl == 0 {
+                    return Ok(p);
+                }
+                lvl -= 1;
+                p.push(PItem::Close);
+            }
+            (Some(I), Some(I), Some(I)) => emit_rna(dna, i, rna),
+            _ => return Err(format!("Unexpected dna when pattern decoding {:?}", tail(dna, *i, 3))),
+        }
+    }
+}
+
+fn template(dna: &[Base], i: &mut usize, rna: &mut Vec<ShortDna>) -> InterpreterResult<Template> {
+    let mut t = Vec::new();
+    loop {
+        match (dna.get(*i), dna.get(*i + 1), dna.get(*i + 2)) {
+            (Some(C), _, _) => { *i += 1; t.push(TItem::TBase(I)); }
+            (Some(F), _, _) => { *i += 1; t.push(TItem::TBase(C)); }
+            (Some(P), _, _) => { *i += 1; t.push(TItem::TBase(F)); }
+            (Some(I), Some(C), _) => { *i += 2; t.push(TItem::TBase(P)); }
+            (Some(I), Some(F), _) | (Some(I), Some(P), _) => {
+                *i += 2;
+                let l = nat(dna, i)?;
+                let n = nat(dna, i)?;
+                t.push(TItem::Ref { n, l });
+            }
+            (Some(I), Some(I), Some(C)) | (Some(I), Some(I), Some(F)) => {
+                *i += 3;
+                return Ok(t);
+            }
+            (Some(I), Some(I), Some(P)) => {
+                *i += 3;
+                t.push(TItem::Len { n: nat(dna, i)? });
+            }
+            (Some(I), Some(I), Some(I)) => emit_rna(dna, i, rna),
+            _ => return Err(format!("Unexpected dna when template decoding {:?}", tail(dna, *i, 3))),
+        }
+    }
+}
+
+/// Bases consumed by the match and the environment, None if it fails.
+fn match_pat(dna: &[Base], pattern: &Pattern) -> Option<(usize, Vec<Vec<Base>>)> {
+    let mut i = 0;
+    let mut env = Vec::new();
+    let mut c = Vec::new();
+    for p in pattern {
+        match p {
+            PItem::PBase(b) => {
+                if dna.get(i) != Some(b) {
+                    return None;
+                }
+                i += 1;
+            }
+            PItem::Skip { n } => {
+                i = i.checked_add(*n).filter(|i| *i <= dna.len())?;
+            }
+            PItem::Search { s } => {
+                let start = (i..=dna.len()).find(|start| dna[*start..].starts_with(s))?;
+                i = start + s.len();
+            }
+            PItem::Open => c.push(i),
+            PItem::Close => {
+                let start = c.pop()?;
+                env.push(dna[start..i].to_vec());
+            }
+        }
+    }
+    Some((i, env))
+}
+
+fn quote(dna: &[Base]) -> Vec<Base> {
+    let mut result = Vec::new();
+    for b in dna {
+        match b {
+            I => result.push(C),
+            C => result.push(F),
+            F => result.push(P),
+            P => result.extend([I, C]),
+        }
+    }
+    result
+}
+
+fn protect(l: usize, dna: &[Base]) -> Vec<Base> {
+    let mut result = dna.to_vec();
+    for _ in 0..l {
+        result = quote(&result);
+    }
+    result
+}
+
+fn asnat(n: usize) -> Vec<Base> {
+    if n == 0 {
+        vec![P]
+    } else {
+        let mut result = vec![match n % 2 { 0 => I, _ => C }];
+        result.extend(asnat(n / 2));
+        result
+    }
+}
+
+fn replace(template: &Template, env: &[Vec<Base>]) -> Vec<Base> {
+    let mut r = Vec::new();
+    for t in template {
+        match t {
+            TItem::TBase(b) => r.push(*b),
+            TItem::Ref { n, l } => r.extend(protect(*l, env.get(*n).map_or(&[][..], |e| e.as_slice()))),
+            TItem::Len { n } => r.extend(asnat(env.get(*n).map_or(0, |e| e.len()))),
+        }
+    }
+    r
+}
+
+pub fn do_step(context: &mut Context) -> InterpreterResult<()> {
+    let mut i = 0;
+    let pattern = pattern(&context.dna, &mut i, &mut context.rna)?;
+    let template = template(&context.dna, &mut i, &mut context.rna)?;
+    let rest = &context.dna[i..];
+    context.dna = match match_pat(rest, &pattern) {
+        Some((matched, env)) => {
+            let mut dna = replace(&template, &env);
+            dna.extend_from_slice(&rest[matched..]);
+            dna
+        }
+        None => rest.to_vec(),
+    };
+    context.step += 1;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::interpreter::dna::Dna;
+    use crate::interpreter::interpreter;
+    use crate::interpreter::step_cache::StepCache;
+
+    // xorshift, to keep the random prefixes reproducible without extra dependencies
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn base(&mut self) -> Base {
+            [I, C, F, P][(self.next() % 4) as usize]
+        }
+    }
+
+    // random bases with a bias towards I, so that skips, searches, references and rna show up
+    fn random_dna(random: &mut Random, len: usize) -> Vec<Base> {
+        let mut dna = Vec::new();
+        while dna.len() < len {
+            match random.next() % 8 {
+                0 | 1 => dna.push(I),
+                2 => dna.extend([I, I, C]),
+                3 => dna.extend([I, I, P]),
+                _ => dna.push(random.base()),
+            }
+        }
+        dna
+    }
+
+    // deep protection levels blow the dna up exponentially in both interpreters
+    fn too_expensive(context: &Context) -> bool {
+        let mut i = 0;
+        let mut rna = Vec::new();
+        let template = pattern(&context.dna, &mut i, &mut rna)
+            .and_then(|_| template(&context.dna, &mut i, &mut rna));
+        match template {
+            Ok(template) => template.iter().any(|t| matches!(t, TItem::Ref { l, .. } if *l > 4)),
+            Err(_) => false,
+        }
+    }
+
+    /// Runs the interpreters, with and without the step cache, for at most `steps` steps,
+    /// checking they agree after every one. Returns the cache hits.
+    fn check_same(dna: Vec<Base>, steps: usize) -> usize {
+        let mut optimised = interpreter::Context::new(Dna::from_slice(&dna));
+        let mut cached = interpreter::Context::new(Dna::from_slice(&dna));
+        let mut cache = StepCache::new();
+        let mut naive = Context::new(dna.clone());
+        for step in 0..steps {
+            if too_expensive(&naive) {
+                break;
+            }
+            let expected = do_step(&mut naive);
+            for (actual, optimised) in [(interpreter::do_step(&mut optimised), &optimised),
+                                        (interpreter::do_step_cached(&mut cached, &mut cache), &cached)] {
+                assert_eq!(actual, expected, "stop reason at step {} for {:?}", step, Dna::from_slice(&dna));
+                assert_eq!(optimised.rna, naive.rna, "rna at step {} for {:?}", step, Dna::from_slice(&dna));
+                if expected.is_ok() {
+                    assert_eq!(optimised.dna.len(), naive.dna.len(), "dna length at step {} for {:?}", step, Dna::from_slice(&dna));
+                    assert_eq!(optimised.dna, Dna::from_slice(&naive.dna), "dna at step {} for {:?}", step, Dna::from_slice(&dna));
+                }
+            }
+            if expected.is_err() {
+                break;
+            }
+        }
+        cache.hits
+    }
+
+    fn bases(dna: &str) -> Vec<Base> {
+        dna.trim().chars().map(|c| Base::from_char(c).unwrap()).collect()
+    }
+
+    #[test]
+    fn random_prefixes_test() {
+        let mut random = Random(0x5eed);
+        for _ in 0..2000 {
+            let len = (random.next() % 200) as usize + 1;
+            let dna = random_dna(&mut random, len);
+            check_same(dna, 50);
+        }
+    }
+
+    #[test]
+    fn repeated_genes_test() {
+        let mut random = Random(0xcac4e);
+        let mut hits = 0;
+        for _ in 0..500 {
+            let len = (random.next() % 60) as usize + 1;
+            let gene = random_dna(&mut random, len);
+            hits += check_same(gene.repeat(8), 50);
+        }
+        // otherwise the cached interpreter is hardly tested
+        assert!(hits > 100, "{} hits", hits);
+    }
+
+    #[test]
+    fn recorded_prefixes_test() {
+        // without Endo's DNA the prefixes would run on their own and prove little
+        let endo = std::fs::read_to_string("data/endo/dna")
+            .map(|dna| bases(&dna))
+            .expect("recorded_prefixes_test needs Endo's DNA in data/endo/dna");
+        let mut tasks = vec![Path::new("data/empty_prefix").to_path_buf(), Path::new("data/health_check").to_path_buf()];
+        tasks.extend(std::fs::read_dir("data/repair_guide").unwrap().map(|entry| entry.unwrap().path()));
+        for task in tasks {
+            if let Ok(prefix) = std::fs::read_to_string(task.join("dna")) {
+                let mut dna = bases(&prefix);
+                dna.extend(&endo);
+                check_same(dna, 20);
+            }
+        }
+    }
+}
@@ -1,19 +1,36 @@
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::time::Instant;
 
-use crate::image::DrawCommand;
+use crate::image::{compress, DrawCommand};
 use crate::interpreter::dna::Dna;
-use crate::interpreter::interpreter::{Context, do_all_steps, do_step};
-use crate::utils::{load, store};
+use crate::interpreter::interpreter::{Context, do_all_steps, do_step_cached};
+use crate::interpreter::profiler::Profiler;
+use crate::interpreter::step_cache::StepCache;
+use crate::cli::Args;
+use crate::utils::store_to;
+#[cfg(test)]
+use crate::utils::load;
 
-pub fn run_with_logs(context: &mut Context) {
+/// Steps go through a `StepCache`, its stats are printed at the end.
+/// With `profile`, steps are also profiled and the report with that many top patterns is printed.
+pub fn run_with_logs(context: &mut Context, limit_steps: Option<usize>, profile: Option<usize>) {
     let start_at = Instant::now();
+    let mut profiler = profile.map(|_| Profiler::new());
+    let mut cache = StepCache::new();
     let mut step = 0;
     loop {
+        if matches!(limit_steps, Some(limit) if step >= limit) {
+            println!("Step limit reached on {:?}", step);
+            break;
+        }
         if step % 10000 == 0 {
             println!("Step: {} Elapsed: {:?}", step, start_at.elapsed());
         }
-        if let Err(err) = do_step(context) {
+        let result = match &mut profiler {
+            Some(profiler) => profiler.step(context, &mut cache),
+            None => do_step_cached(context, &mut cache),
+        };
+        if let Err(err) = result {
             println!("Finish with: {:?} on {:?}", err, step);
             break;
         }
@@ -22,59 +39,77 @@ pub fn run_with_logs(context: &mut Context) {
         }
         step += 1;
     }
+    if let (Some(profiler), Some(top)) = (profiler, profile) {
+        println!("{}", profiler.report(top));
+    }
+    println!("Step cache: {}", cache);
+}
+
+fn read_dna<P: AsRef<Path>>(path: P) -> Result<Dna, String> {
+    let path = path.as_ref();
+    let dna_str = std::fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    return Dna::from_string(&dna_str);
 }
 
-fn read_dna<P: AsRef<Path>>(path: P) -> Dna {
-    let dna_str = &std::fs::read_to_string(path).unwrap();
-    return Dna::from_string(&dna_str).unwrap();
+/// Prefix followed by Endo's DNA, without panicking if anything is missing.
+pub fn dna_for_prefix<P: AsRef<Path>>(data_dir: P, prefix: &str) -> Result<Dna, String> {
+    let endo_dna = read_dna(data_dir.as_ref().join("endo").join("dna"))?;
+    return Ok(Dna::from_string(prefix)?.concat(&endo_dna));
 }
 
-fn dna_for_task<S: AsRef<str>>(task: S) -> Dna {
-    let endo_dna = read_dna("data/endo/dna");
-    let prefix_dna = read_dna(["data", task.as_ref(), "dna"].iter().collect::<PathBuf>());
-    return prefix_dna.concat(&endo_dna);
+/// Task's prefix from `<data_dir>/<task>/dna` followed by Endo's DNA.
+pub fn dna_for_task<P: AsRef<Path>, S: AsRef<str>>(data_dir: P, task: S) -> Result<Dna, String> {
+    let endo_dna = read_dna(data_dir.as_ref().join("endo").join("dna"))?;
+    let prefix_dna = read_dna(data_dir.as_ref().join(task.as_ref()).join("dna"))?;
+    return Ok(prefix_dna.concat(&endo_dna));
 }
 
-crate::entry_point!("interpreter", interpreter_main);
-fn interpreter_main() {
-    let task = std::env::args().nth(2).expect("Not enough arguments");
+crate::entry_point!("interpreter", interpreter_main,
+                    "<task> [--data-dir <dir>] [--out <dir>] [--limit-steps <n>] [--profile <top>]",
+                    "Runs the task's prefix with Endo's DNA, stores commands, compact commands and steps");
+fn interpreter_main(args: &Args) -> Result<(), String> {
+    let task = args.arg("task");
     println!("Run interpreter on {}", task);
-    let mut context = Context::new(dna_for_task(&task));
-    run_with_logs(&mut context);
+    let mut context = Context::new(dna_for_task(args.data_dir(), task)?);
+    run_with_logs(&mut context, args.parse_value("limit-steps")?, args.parse_value("profile")?);
 
 //     store(&context, [&folder, "context.ron"].iter().collect::<PathBuf>());
 
     println!("Produced: {} operations", context.rna.len());
-    let commands: Vec<_> = context.rna.iter().filter_map(|dna| {
-        DrawCommand::decode(dna)
-    }).collect();
+    let (commands, steps) = context.draw_commands_with_steps();
     println!("Valid: {} commands", commands.len());
-    store(&commands, [&task, "commands.ron"].iter().collect::<PathBuf>());
+    let out_dir = args.out_dir().join(task);
+    store_to(&commands, out_dir.join("commands.ron"));
+    store_to(&compress(&commands), out_dir.join("compact.ron"));
+    store_to(&steps, out_dir.join("steps.ron"));
+    Ok(())
 }
 
-fn produce_draw_commands(dna: Dna) -> Vec<DrawCommand> {
+pub fn produce_draw_commands(dna: Dna) -> Vec<DrawCommand> {
     let mut context = Context::new(dna);
     do_all_steps(&mut context);
     return context.draw_commands();
 }
 
+#[cfg(test)]
 fn check_for<P: AsRef<str>, S: AsRef<str>>(task: P, task_name: S) {
-    let dna = dna_for_task(&task);
+    let dna = dna_for_task("data", &task).unwrap();
     let start_time = Instant::now();
     let actual_commands = produce_draw_commands(dna);
     println!("{} took: {:?}", task_name.as_ref(), start_time.elapsed());
-    let expected_commands: Vec<DrawCommand> = load(["data", task.as_ref(), "commands.ron"].iter().collect::<PathBuf>());
+    let expected_commands: Vec<DrawCommand> = load(Path::new("data").join(task.as_ref()).join("commands.ron"));
     assert_eq!(expected_commands, actual_commands);
 }
 
 #[test]
 fn health_check_test() {
     check_for("health_check", "Health check");
-    check_for("repair_guide/initial", "Repair guide");
+    check_for("repair_guide/initial_0", "Repair guide");
 }
 
 #[test]
 fn bench() {
     //65.00281809s
-    check_for("repair_topics", "Repair topics");
+    check_for("repair_guide/topics_1", "Repair topics");
 }
\ No newline at end of file
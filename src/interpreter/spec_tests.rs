@@ -0,0 +1,163 @@
+//! Conformance with the examples and rules of the Endo DNA specification,
+//! self-contained so that it runs without anything under `data/`.
+use crate::interpreter::dna::{Base, Dna};
+use crate::interpreter::interpreter::{do_step, Context};
+use crate::interpreter::literals::{asnat, consts, nat, protect};
+use crate::interpreter::pattern::{self, pattern, PItem};
+use crate::interpreter::template::{self, template, TItem};
+
+use Base::*;
+
+fn context(dna: &str) -> Context {
+    Context::new(Dna::from_string(dna).unwrap())
+}
+
+fn bases(dna: &str) -> Vec<Base> {
+    dna.chars().map(|c| Base::from_char(c).unwrap()).collect()
+}
+
+fn search(s: &str) -> PItem {
+    PItem::Search { s: bases(s) }
+}
+
+fn decode_pattern(dna: &str) -> (Result<Vec<PItem>, String>, String) {
+    let mut context = context(dna);
+    let p = pattern(&mut context);
+    (p, format!("{:?}", context.dna))
+}
+
+fn decode_template(dna: &str) -> (Result<Vec<TItem>, String>, String) {
+    let mut context = context(dna);
+    let t = template(&mut context);
+    (t, format!("{:?}", context.dna))
+}
+
+fn step(dna: &str) -> String {
+    let mut context = context(dna);
+    do_step(&mut context).unwrap();
+    format!("{:?}", context.dna)
+}
+
+#[test]
+fn quote_and_protect_test() {
+    let quoted = |dna: &str, l| format!("{:?}", protect(l, &Dna::from_string(dna).unwrap()));
+    assert_eq!(quoted("I", 1), "C");
+    assert_eq!(quoted("C", 1), "F");
+    assert_eq!(quoted("F", 1), "P");
+    assert_eq!(quoted("P", 1), "IC");
+    assert_eq!(quoted("ICFP", 0), "ICFP");
+    assert_eq!(quoted("ICFP", 2), "FPICCF");
+    assert_eq!(quoted("", 3), "");
+}
+
+#[test]
+fn asnat_test() {
+    assert_eq!(asnat(0), bases("P"));
+    assert_eq!(asnat(1), bases("CP"));
+    assert_eq!(asnat(2), bases("ICP"));
+    assert_eq!(asnat(5), bases("CICP"));
+}
+
+#[test]
+fn nat_test() {
+    let mut c = context("ICFCP");
+    assert_eq!(nat(&mut c), Ok(10));
+    assert_eq!(format!("{:?}", c.dna), "");
+    assert_eq!(nat(&mut context("PC")), Ok(0));
+    assert_eq!(nat(&mut context("FFCP")), Ok(4));
+    // finish when the dna runs out before P
+    assert!(nat(&mut context("CIC")).is_err());
+}
+
+#[test]
+fn consts_test() {
+    let mut c = context("CFPICIIC");
+    assert_eq!(consts(&mut c), bases("ICFP"));
+    assert_eq!(format!("{:?}", c.dna), "IIC");
+    let mut c = context("IF");
+    assert_eq!(consts(&mut c), bases(""));
+    assert_eq!(format!("{:?}", c.dna), "IF");
+}
+
+#[test]
+fn pattern_examples_test() {
+    assert_eq!(decode_pattern("CIIC").0, Ok(vec![PItem::PBase(I)]));
+    assert_eq!(decode_pattern("IIPIPICPIICICIIF").0,
+               Ok(vec![PItem::Open, PItem::Skip { n: 2 }, PItem::Close, PItem::PBase(P)]));
+}
+
+#[test]
+fn pattern_branches_test() {
+    assert_eq!(decode_pattern("CFPICIICFP"),
+               (Ok(vec![PItem::PBase(I), PItem::PBase(C), PItem::PBase(F), PItem::PBase(P)]), "FP".to_string()));
+    assert_eq!(decode_pattern("IPICPIIC").0, Ok(vec![PItem::Skip { n: 2 }]));
+    // the base after IF is skipped whatever it is
+    assert_eq!(decode_pattern("IFCCFICIIC").0, Ok(vec![search("ICP")]));
+    assert_eq!(decode_pattern("IFPCIIC").0, Ok(vec![search("I")]));
+    assert_eq!(decode_pattern("IIPIIPIICIIFIIC").0,
+               Ok(vec![PItem::Open, PItem::Open, PItem::Close, PItem::Close]));
+    // both IIC and IIF end the pattern on level 0
+    assert_eq!(decode_pattern("CIICF"), (Ok(vec![PItem::PBase(I)]), "F".to_string()));
+    assert_eq!(decode_pattern("CIIFF"), (Ok(vec![PItem::PBase(I)]), "F".to_string()));
+}
+
+#[test]
+fn pattern_rna_and_finish_test() {
+    let mut c = context("IIICFPICFPCIIC");
+    assert_eq!(pattern(&mut c), Ok(vec![PItem::PBase(I)]));
+    assert_eq!(c.rna, vec![bases("CFPICFP")]);
+    assert!(decode_pattern("").0.is_err());
+    assert!(decode_pattern("C").0.is_err());
+    assert!(decode_pattern("CII").0.is_err());
+    assert!(decode_pattern("IP").0.is_err());
+}
+
+#[test]
+fn template_branches_test() {
+    assert_eq!(decode_template("CFPICIICFP"),
+               (Ok(vec![TItem::TBase(I), TItem::TBase(C), TItem::TBase(F), TItem::TBase(P)]), "FP".to_string()));
+    // level comes first, then the number of the reference
+    assert_eq!(decode_template("IFCPICPIIC").0, Ok(vec![TItem::Ref { n: 2, l: 1 }]));
+    assert_eq!(decode_template("IPPCPIIC").0, Ok(vec![TItem::Ref { n: 1, l: 0 }]));
+    assert_eq!(decode_template("IIPCCPIIC").0, Ok(vec![TItem::Len { n: 3 }]));
+    assert_eq!(decode_template("CIIFF"), (Ok(vec![TItem::TBase(I)]), "F".to_string()));
+    let mut c = context("IIIPPPPPPPCIIC");
+    assert_eq!(template(&mut c), Ok(vec![TItem::TBase(I)]));
+    assert_eq!(c.rna, vec![bases("PPPPPPP")]);
+    assert!(decode_template("IFCP").0.is_err());
+    assert!(decode_template("II").0.is_err());
+}
+
+#[test]
+fn encode_test() {
+    let p = vec![PItem::PBase(I), PItem::PBase(P), PItem::Skip { n: 2 }, search("ICFP"),
+                 PItem::Open, PItem::Close];
+    assert_eq!(pattern::encode(&p), bases("CICIPICPIFFCFPICIIPIIC"));
+    let t = vec![TItem::TBase(F), TItem::TBase(P), TItem::Ref { n: 2, l: 1 }, TItem::Len { n: 0 }];
+    assert_eq!(template::encode(&t), bases("PICIFCPICPIIPP"));
+}
+
+#[test]
+fn step_examples_test() {
+    assert_eq!(step("IIPIPICPIICICIIFICCIFPPIICCFPC"), "PICFC");
+    assert_eq!(step("IIPIPICPIICICIIFICCIFCCCPPIICCFPC"), "PIICCFCFFPC");
+    assert_eq!(step("IIPIPIICPIICIICCIICFCFC"), "I");
+}
+
+#[test]
+fn matching_test() {
+    // a failed match only drops the pattern and the template
+    assert_eq!(step("FIICCIICICFP"), "ICFP");
+    // search moves to the end of the first occurrence, or fails
+    assert_eq!(step("IFFFPIICIICICFPFFP"), "PFFP");
+    assert_eq!(step("IFFFPIICIICICIIC"), "ICIIC");
+    // skipping right to the end matches, past the end fails
+    assert_eq!(step("IPCCPIICIICICF"), "");
+    assert_eq!(step("IPIICPIICIICICF"), "ICF");
+    // references to missing groups are empty, their length is zero
+    assert_eq!(step("IIPCIICIICIPPCPIIPCPIICIFF"), "PFF");
+    // references are protected on their level
+    assert_eq!(step("IIPCIICIICIPPPIICIP"), "IP");
+    assert_eq!(step("IIPCIICIICIFCPPIICIP"), "CP");
+    assert_eq!(step("IIPCIICIICIIPPIICIP"), "CPP");
+}
@@ -0,0 +1,119 @@
+//! Endo's DNA runs the same genes over and over, so the same pattern and template
+//! get decoded again and again. The cache remembers what a run of bases decoded to.
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use crate::interpreter::dna::{Dna, ShortDna};
+use crate::interpreter::interpreter::{decode, Context, InterpreterResult, StepObserver};
+use crate::interpreter::pattern::Pattern;
+use crate::interpreter::template::Template;
+
+// dropped all at once beyond that, gene code that ran recently is cached again quickly
+const MAX_ENTRIES: usize = 1 << 16;
+
+struct Decoded {
+    // every base read by the decoders, a successful decode never looks past them
+    consumed: Dna,
+    pattern: Pattern,
+    template: Template,
+    rna: Vec<ShortDna>,
+}
+
+/// Decoded patterns and templates, keyed by the first 32 bases they were decoded from.
+pub struct StepCache {
+    entries: HashMap<(u64, usize), Vec<Decoded>>,
+    len: usize,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl StepCache {
+    pub fn new() -> Self {
+        StepCache { entries: HashMap::new(), len: 0, hits: 0, misses: 0 }
+    }
+
+    /// Same as `decode`, including the rna it emits, which is only called on a miss.
+    /// Failed decodes are not cached.
+    pub fn decode_with<D>(&mut self, context: &mut Context, decode: D) -> InterpreterResult<(Pattern, Template)>
+    where
+        D: FnOnce(&mut Context) -> InterpreterResult<(Pattern, Template)>,
+    {
+        let key = context.dna.head();
+        if let Some(decoded) = self.entries.get(&key)
+            .and_then(|entries| entries.iter().find(|decoded| context.dna.starts_with(&decoded.consumed))) {
+            self.hits += 1;
+            for rna in &decoded.rna {
+                context.append_rna(rna.clone());
+            }
+            context.dna.skip(decoded.consumed.len());
+            return Ok((decoded.pattern.clone(), decoded.template.clone()));
+        }
+        self.misses += 1;
+        let dna = context.dna.clone();
+        let rna_from = context.rna.len();
+        let (p, t) = decode(context)?;
+        if self.len >= MAX_ENTRIES {
+            self.entries.clear();
+            self.len = 0;
+        }
+        self.len += 1;
+        self.entries.entry(key).or_default().push(Decoded {
+            consumed: dna.subseq(0..dna.len() - context.dna.len()),
+            pattern: p.clone(),
+            template: t.clone(),
+            rna: context.rna[rna_from..].to_vec(),
+        });
+        Ok((p, t))
+    }
+}
+
+impl Default for StepCache {
+    fn default() -> Self {
+        StepCache::new()
+    }
+}
+
+impl StepObserver for StepCache {
+    fn decode(&mut self, context: &mut Context) -> InterpreterResult<(Pattern, Template)> {
+        self.decode_with(context, decode)
+    }
+}
+
+impl Display for StepCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lookups = (self.hits + self.misses).max(1);
+        write!(f, "{} hits, {} misses ({:.1}% hits), {} entries",
+               self.hits, self.misses, 100.0 * self.hits as f64 / lookups as f64, self.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::dna::Base::*;
+    use crate::interpreter::interpreter::{do_step, do_step_cached};
+    use crate::interpreter::pattern::{self, PItem};
+    use crate::interpreter::template::{self, TItem};
+
+    #[test]
+    fn repeated_gene_test() {
+        let end = [I, I, C];
+        // emits rna, then (PPPP) -> the same group
+        let gene = [vec![I, I, I, C, F, P, I, C, F, P],
+                    pattern::encode(&vec![PItem::Open, PItem::Search { s: vec![P; 4] }, PItem::Close]), end.to_vec(),
+                    template::encode(&vec![TItem::Ref { n: 0, l: 0 }]), end.to_vec()].concat();
+        let dna = [gene.repeat(5), vec![P; 4]].concat();
+        let mut cached = Context::new(Dna::from_slice(&dna));
+        let mut plain = Context::new(Dna::from_slice(&dna));
+        let mut cache = StepCache::new();
+        for _ in 0..6 {
+            assert_eq!(do_step_cached(&mut cached, &mut cache), do_step(&mut plain));
+            assert_eq!(cached.dna, plain.dna);
+        }
+        assert_eq!(cached.rna, plain.rna);
+        assert_eq!(cached.rna_steps, plain.rna_steps);
+        assert_eq!(cached.rna.len(), 5);
+        // the last step fails to decode, it is a miss which is not cached
+        assert_eq!((cache.hits, cache.misses, cache.len), (4, 2, 1));
+    }
+}
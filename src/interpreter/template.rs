@@ -42,8 +42,8 @@ impl TItem {
             },
             TItem::Ref { n, l } => {
                 result.extend_from_slice(&[I, F]);
-                result.extend_from_slice(&asnat(*n));
                 result.extend_from_slice(&asnat(*l));
+                result.extend_from_slice(&asnat(*n));
             },
             TItem::Len { n } => {
                 result.extend_from_slice(&[I, I, P]);
@@ -0,0 +1,79 @@
+//! Interpreter for Endo's DNA and renderer for the RNA it produces.
+//!
+//! The public API covers the interpreter (`interpreter`), draw commands (`image`),
+//! the renderer (`drawer`) and the prefix tools (`catalog_pages`, `call_gen`).
+//! Everything else is used by the `main` binary entry points, the hidden modules are
+//! public only for `main`, the benches and the fuzz targets.
+
+#[doc(hidden)]
+pub mod cli;
+#[doc(hidden)]
+pub mod utils;
+pub mod image;
+pub mod drawer;
+pub mod interpreter;
+#[cfg(feature = "gui")]
+mod gui;
+pub mod catalog_pages;
+pub mod call_gen;
+mod diff_render;
+mod stats;
+// the headless model of the gui, only its tests use it without the gui
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+mod snapshots;
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+mod session;
+mod golden;
+
+pub use crate::drawer::Drawer;
+pub use crate::image::DrawCommand;
+pub use crate::interpreter::dna::{Base, Dna};
+pub use crate::interpreter::interpreter::{do_step, Context};
+
+
+#[linkme::distributed_slice]
+pub static ENTRY_POINTS: [cli::EntryPoint] = [..];
+
+/**
+Entry points declare their arguments and a help line, and get them parsed:
+```ignore
+crate::entry_point!("hello", hello, "<name> [--out <dir>]", "Greets <name>");
+fn hello(args: &Args) -> Result<(), String> { ... }
+```
+If you define more than one entry point in one module,
+for technical reasons you need to specify unique identifier names:
+```ignore
+crate::entry_point!("hello1", hello1, _EP_HELLO1, "<name>", "Greets <name> once");
+fn hello1(args: &Args) -> Result<(), String> { ... }
+crate::entry_point!("hello2", hello2, _EP_HELLO2, "<name>", "Greets <name> twice");
+fn hello2(args: &Args) -> Result<(), String> { ... }
+```
+ */
+#[macro_export]
+macro_rules! entry_point {
+    ($name:expr, $f:expr, $static_name:ident, $usage:expr, $help:expr) => {
+        #[linkme::distributed_slice($crate::ENTRY_POINTS)]
+        static $static_name: $crate::cli::EntryPoint = $crate::cli::EntryPoint {
+            name: $name,
+            usage: $usage,
+            help: $help,
+            run: $f,
+        };
+    };
+    ($name:expr, $f:expr, $usage:expr, $help:expr) => {
+        $crate::entry_point!($name, $f, _ENTRY_POINT, $usage, $help);
+    };
+}
+
+pub fn ensure_entry_points_unique() {
+    for (i, entry_point) in ENTRY_POINTS.iter().enumerate() {
+        for other in &ENTRY_POINTS[..i] {
+            assert_ne!(entry_point.name, other.name, "duplicate entry point names");
+        }
+    }
+}
+
+#[test]
+fn entry_points_unique() {
+    ensure_entry_points_unique();
+}
@@ -0,0 +1,228 @@
+use std::cmp::min;
+
+use crate::drawer::Drawer;
+use crate::image::{CompactCommand, DrawCommand};
+use crate::snapshots::Snapshots;
+
+const KEYFRAME_INTERVAL: usize = 1000;
+// a layer takes 1.4MB, 4.3MB with provenance
+const KEYFRAMES_BYTES: usize = 512 << 20;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PaintedBy {
+    pub row: usize,
+    pub command: CompactCommand,
+    // interpreter step which emitted the command, if known
+    pub step: Option<usize>,
+}
+
+/// Stepping and seeking state of the viewer, independent of any frontend.
+/// The drawer state shown is the one after applying the current command.
+pub struct Session {
+    commands: Vec<CompactCommand>,
+    // index of the first plain command of every compact one
+    offsets: Vec<usize>,
+    // interpreter steps which emitted the plain commands, if known
+    steps: Option<Vec<usize>>,
+    current_command: usize,
+    current_layer: usize,
+    snapshots: Snapshots,
+    // bumped every time the shown drawer state changes
+    generation: u64,
+}
+
+impl Session {
+    /// With `provenance` it also records which command painted every pixel, see `who_painted`,
+    /// which triples the memory of every drawer state.
+    pub fn new(commands: Vec<CompactCommand>, steps: Option<Vec<usize>>, provenance: bool) -> Self {
+        let initial = if provenance { Drawer::with_provenance() } else { Drawer::new() };
+        let offsets = commands.iter().scan(0, |offset, command| {
+            let start = *offset;
+            *offset += command.len();
+            Some(start)
+        }).collect();
+        let mut session = Session {
+            commands,
+            offsets,
+            steps,
+            current_command: 0,
+            current_layer: 0,
+            snapshots: Snapshots::new(initial, KEYFRAME_INTERVAL, KEYFRAMES_BYTES),
+            generation: 0,
+        };
+        session.refresh();
+        session
+    }
+
+    /// Starts over with other commands, generations keep growing.
+    pub fn reset(&mut self, commands: Vec<CompactCommand>, steps: Option<Vec<usize>>) {
+        let generation = self.generation;
+        *self = Session::new(commands, steps, self.drawer().records_provenance());
+        self.generation += generation;
+    }
+
+    pub fn commands(&self) -> &[CompactCommand] {
+        &self.commands
+    }
+
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    pub fn current_command(&self) -> usize {
+        self.current_command
+    }
+
+    pub fn current_layer(&self) -> usize {
+        self.current_layer
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn drawer(&self) -> &Drawer {
+        self.snapshots.current()
+    }
+
+    fn refresh(&mut self) {
+        let drawer_state_index = min(self.current_command + 1, self.commands.len());
+        let layers = self.snapshots.state(&self.commands, drawer_state_index).bitmaps.len();
+        self.current_layer = min(self.current_layer, layers - 1);
+        self.generation += 1;
+    }
+
+    pub fn seek(&mut self, row: usize) {
+        self.current_command = min(row, self.commands.len().saturating_sub(1));
+        self.refresh();
+    }
+
+    pub fn step_forward(&mut self, n: usize) {
+        if self.current_command + n < self.commands.len() {
+            self.current_command += n;
+        }
+        self.refresh();
+    }
+
+    pub fn step_back(&mut self, n: usize) {
+        if self.current_command >= n {
+            self.current_command -= n;
+        }
+        self.refresh();
+    }
+
+    pub fn select_layer<F: FnOnce(usize) -> usize>(&mut self, f: F) {
+        let layers = self.drawer().bitmaps.len();
+        self.current_layer = min(f(self.current_layer), layers - 1);
+    }
+
+    /// Appends freshly emitted commands and moves to the last one.
+    pub fn push_commands(&mut self, commands: Vec<(DrawCommand, usize)>) {
+        if commands.is_empty() {
+            return;
+        }
+        for (command, step) in commands {
+            let offset = self.offsets.last().zip(self.commands.last())
+                .map_or(0, |(offset, last)| offset + last.len());
+            self.offsets.push(offset);
+            self.commands.push(command.into());
+            if let Some(steps) = &mut self.steps {
+                steps.push(step);
+            }
+        }
+        self.seek(self.commands.len() - 1);
+    }
+
+    /// None if the pixel was never painted or the session was created without provenance.
+    pub fn who_painted(&self, layer: usize, x: u32, y: u32) -> Option<PaintedBy> {
+        let idx = self.drawer().who_painted(layer, x, y)?;
+        let row = self.offsets.partition_point(|offset| *offset <= idx) - 1;
+        Some(PaintedBy {
+            row,
+            command: self.commands[row],
+            step: self.steps.as_ref().and_then(|steps| steps.get(idx).cloned()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        let commands = vec![CompactCommand::MoveN(5),
+                            CompactCommand::Command(DrawCommand::Line),
+                            CompactCommand::Command(DrawCommand::AddBitmap),
+                            CompactCommand::Command(DrawCommand::AddBitmap)];
+        Session::new(commands, Some(vec![0, 0, 0, 0, 0, 1, 2, 3]), true)
+    }
+
+    #[test]
+    fn stepping_test() {
+        let mut session = session();
+        assert_eq!(session.current_command(), 0);
+        assert_eq!(session.drawer().position(), (5, 0));
+        session.step_forward(2);
+        assert_eq!(session.current_command(), 2);
+        assert_eq!(session.drawer().bitmaps.len(), 2);
+        session.step_forward(2);
+        assert_eq!(session.current_command(), 2);
+        session.step_back(3);
+        assert_eq!(session.current_command(), 2);
+        session.step_back(1);
+        assert_eq!(session.current_command(), 1);
+        session.seek(100);
+        assert_eq!(session.current_command(), 3);
+        assert_eq!(session.drawer().bitmaps.len(), 3);
+    }
+
+    #[test]
+    fn layer_selection_test() {
+        let mut session = session();
+        session.seek(3);
+        session.select_layer(|_| 10);
+        assert_eq!(session.current_layer(), 2);
+        session.seek(0);
+        assert_eq!(session.current_layer(), 0);
+    }
+
+    #[test]
+    fn generation_test() {
+        let mut session = session();
+        let generation = session.generation();
+        session.seek(2);
+        assert!(session.generation() > generation);
+        let generation = session.generation();
+        session.reset(Vec::new(), None);
+        assert!(session.generation() > generation);
+        assert_eq!(session.current_command(), 0);
+        assert_eq!(session.drawer(), &Drawer::new());
+        assert!(session.drawer().records_provenance());
+    }
+
+    #[test]
+    fn who_painted_test() {
+        let mut session = session();
+        session.seek(1);
+        assert_eq!(session.who_painted(0, 3, 0), Some(PaintedBy {
+            row: 1,
+            command: CompactCommand::Command(DrawCommand::Line),
+            step: Some(1),
+        }));
+        assert_eq!(session.who_painted(0, 6, 0), None);
+        let mut plain = Session::new(session.commands().to_vec(), None, false);
+        plain.seek(1);
+        assert!(!plain.drawer().records_provenance());
+        assert_eq!(plain.who_painted(0, 3, 0), None);
+    }
+
+    #[test]
+    fn push_commands_test() {
+        let mut session = Session::new(Vec::new(), Some(Vec::new()), true);
+        session.push_commands(vec![(DrawCommand::Move, 7), (DrawCommand::Line, 9)]);
+        assert_eq!(session.commands().len(), 2);
+        assert_eq!(session.offsets(), &[0, 1]);
+        assert_eq!(session.current_command(), 1);
+        assert_eq!(session.who_painted(0, 0, 0).map(|p| p.step), Some(Some(9)));
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use crate::drawer::Drawer;
+use crate::image::CompactCommand;
+
+struct Keyframe {
+    drawer: Drawer,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// Drawer states for seeking in a command stream with bounded memory.
+/// A keyframe is kept every `interval` commands, taking at most `max_bytes` together,
+/// the least recently used ones are evicted first, except the initial one which
+/// is always kept. Any other state is
+/// replayed forward from the nearest keyframe (or the last requested state).
+pub struct Snapshots {
+    interval: usize,
+    max_bytes: usize,
+    // taken by all the keyframes
+    bytes: usize,
+    keyframes: HashMap<usize, Keyframe>,
+    clock: u64,
+    // last requested state and the number of commands applied to get it
+    current: (usize, Drawer),
+}
+
+impl Snapshots {
+    pub fn new(initial: Drawer, interval: usize, max_bytes: usize) -> Self {
+        assert!(interval > 0);
+        let bytes = initial.size_in_bytes();
+        let mut keyframes = HashMap::new();
+        keyframes.insert(0, Keyframe { drawer: initial.clone(), bytes, last_used: 0 });
+        Snapshots {
+            interval,
+            max_bytes,
+            bytes,
+            keyframes,
+            clock: 0,
+            current: (0, initial),
+        }
+    }
+
+    pub fn current(&self) -> &Drawer {
+        &self.current.1
+    }
+
+    #[cfg(test)]
+    pub fn keyframes_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// State of the drawer after applying the first `index` commands.
+    pub fn state(&mut self, commands: &[CompactCommand], index: usize) -> &Drawer {
+        assert!(index <= commands.len());
+        self.clock += 1;
+        if self.current.0 != index {
+            self.seek_start(index);
+            while self.current.0 < index {
+                self.current.1.apply(commands[self.current.0]);
+                self.current.0 += 1;
+                let at = self.current.0;
+                let since_keyframe = at % self.interval;
+                if since_keyframe == 0 && !self.keyframes.contains_key(&at) {
+                    self.insert_keyframe(at, self.current.1.clone());
+                }
+            }
+        }
+        &self.current.1
+    }
+
+    // Moves `current` back to the nearest keyframe before `index`,
+    // unless it is already between that keyframe and `index`.
+    fn seek_start(&mut self, index: usize) {
+        let mut k = index / self.interval * self.interval;
+        let keyframe_at = loop {
+            if self.keyframes.contains_key(&k) {
+                break k;
+            }
+            // keyframe 0 is never evicted
+            k -= self.interval;
+        };
+        if self.current.0 <= index && self.current.0 >= keyframe_at {
+            return;
+        }
+        let keyframe = self.keyframes.get_mut(&keyframe_at).unwrap();
+        keyframe.last_used = self.clock;
+        self.current = (keyframe_at, keyframe.drawer.clone());
+    }
+
+    // Evicts keyframes until the new one fits, it is dropped if it can't fit at all.
+    fn insert_keyframe(&mut self, at: usize, drawer: Drawer) {
+        let bytes = drawer.size_in_bytes();
+        while self.bytes + bytes > self.max_bytes {
+            let oldest = self.keyframes.iter()
+                .filter(|(at, _)| **at != 0)
+                .min_by_key(|(_, keyframe)| keyframe.last_used)
+                .map(|(at, _)| *at);
+            match oldest {
+                Some(oldest) => self.bytes -= self.keyframes.remove(&oldest).unwrap().bytes,
+                None => return,
+            }
+        }
+        self.bytes += bytes;
+        self.keyframes.insert(at, Keyframe { drawer, bytes, last_used: self.clock });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{Color, DrawCommand, Rgb};
+
+    fn commands() -> Vec<CompactCommand> {
+        let pattern = [DrawCommand::Move, DrawCommand::Line, DrawCommand::TurnC,
+                       DrawCommand::AddColor(Color::Rgb(Rgb::Green)), DrawCommand::Mark,
+                       DrawCommand::Move, DrawCommand::Line, DrawCommand::AddBitmap];
+        pattern.iter().cycle().take(200).map(|c| CompactCommand::from(*c)).collect()
+    }
+
+    fn replay(commands: &[CompactCommand], index: usize) -> Drawer {
+        let mut drawer = Drawer::new();
+        drawer.apply_all(&commands[..index]);
+        drawer
+    }
+
+    #[test]
+    fn seek_test() {
+        let commands = commands();
+        let layer = Drawer::new().size_in_bytes();
+        // from no room for keyframes besides the initial one, to room for a few deep ones
+        for max_bytes in [layer, 12 * layer, 40 * layer] {
+            let mut snapshots = Snapshots::new(Drawer::new(), 16, max_bytes);
+            for index in [0, 150, 151, 37, 200, 3, 64, 199] {
+                assert_eq!(snapshots.state(&commands, index), &replay(&commands, index));
+                assert!(snapshots.keyframes_bytes() <= max_bytes);
+            }
+        }
+    }
+}
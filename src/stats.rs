@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::drawer::{Bounds, Drawer};
+use crate::image::DrawCommand;
+use crate::cli::Args;
+use crate::utils::{read, store_to};
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CommandStats {
+    pub total: usize,
+    pub per_command: BTreeMap<String, usize>,
+    pub max_bucket_size: usize,
+    pub max_stack_depth: usize,
+    pub fills: usize,
+    pub lines: usize,
+    pub turtle_distance: usize,
+    pub drawn_bounds: Option<Bounds>,
+}
+
+// Manhattan distance on the torus of the bitmap, the turtle wraps around its edges
+fn wrapped_distance((x0, y0): (i32, i32), (x1, y1): (i32, i32), (width, height): (i32, i32)) -> usize {
+    let along = |d: i32, size: i32| d.abs().min(size - d.abs()) as usize;
+    along(x1 - x0, width) + along(y1 - y0, height)
+}
+
+pub fn command_stats(commands: &[DrawCommand]) -> CommandStats {
+    let mut drawer = Drawer::with_bounds();
+    let size = (drawer.bitmaps[0].width() as i32, drawer.bitmaps[0].height() as i32);
+    let mut per_command = BTreeMap::new();
+    let mut max_bucket_size = 0;
+    let mut max_stack_depth = drawer.bitmaps.len();
+    let mut turtle_distance = 0;
+    for command in commands {
+        *per_command.entry(command.name().to_string()).or_insert(0) += 1;
+        let position = drawer.position();
+        drawer.apply(*command);
+        turtle_distance += wrapped_distance(position, drawer.position(), size);
+        max_bucket_size = max_bucket_size.max(drawer.bucket_len());
+        max_stack_depth = max_stack_depth.max(drawer.bitmaps.len());
+    }
+    let count = |name: &str| per_command.get(name).cloned().unwrap_or(0);
+    CommandStats {
+        total: commands.len(),
+        max_bucket_size,
+        max_stack_depth,
+        fills: count("TryFill"),
+        lines: count("Line"),
+        turtle_distance,
+        drawn_bounds: drawer.drawn_bounds(),
+        per_command,
+    }
+}
+
+crate::entry_point!("stats", stats_main, "<task> [--out <dir>]",
+                    "Summarises the task's commands, stores stats.ron and stats.json");
+fn stats_main(args: &Args) -> Result<(), String> {
+    let out_dir = args.out_dir().join(args.arg("task"));
+    let commands: Vec<DrawCommand> = read(out_dir.join("commands.ron"))?;
+    let stats = command_stats(&commands);
+    store_to(&stats, out_dir.join("stats.ron"));
+    let file = std::fs::File::create(out_dir.join("stats.json")).map_err(|err| err.to_string())?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), &stats).map_err(|err| err.to_string())?;
+    println!("{}", serde_json::to_string_pretty(&stats).map_err(|err| err.to_string())?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{Color, Rgb};
+
+    #[test]
+    fn command_stats_test() {
+        let commands = [
+            DrawCommand::AddColor(Color::Rgb(Rgb::Red)),
+            DrawCommand::AddColor(Color::Rgb(Rgb::Red)),
+            DrawCommand::Move,
+            DrawCommand::Move,
+            DrawCommand::Mark,
+            DrawCommand::TurnC,
+            DrawCommand::Move,
+            DrawCommand::Line,
+            DrawCommand::ClearBucket,
+            DrawCommand::AddBitmap,
+            DrawCommand::AddBitmap,
+            DrawCommand::Compose,
+        ];
+        let stats = command_stats(&commands);
+        assert_eq!(stats.total, 12);
+        assert_eq!(stats.per_command["AddColor"], 2);
+        assert_eq!(stats.per_command["Move"], 3);
+        assert_eq!(stats.max_bucket_size, 2);
+        assert_eq!(stats.max_stack_depth, 3);
+        assert_eq!(stats.lines, 1);
+        assert_eq!(stats.fills, 0);
+        assert_eq!(stats.turtle_distance, 3);
+        assert_eq!(stats.drawn_bounds, Some(Bounds { min_x: 2, min_y: 0, max_x: 2, max_y: 1 }));
+    }
+
+    #[test]
+    fn turtle_distance_test() {
+        // crossing an edge of the bitmap is a single pixel
+        let west = [DrawCommand::TurnC, DrawCommand::TurnC, DrawCommand::Move, DrawCommand::Move];
+        assert_eq!(command_stats(&west).turtle_distance, 2);
+        let north = [DrawCommand::TurnCC, DrawCommand::Move, DrawCommand::TurnCC, DrawCommand::Move];
+        assert_eq!(command_stats(&north).turtle_distance, 2);
+        assert_eq!(wrapped_distance((0, 0), (300, 599), (600, 600)), 301);
+        assert_eq!(command_stats(&[DrawCommand::Mark, DrawCommand::Line]).turtle_distance, 0);
+    }
+}
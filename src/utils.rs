@@ -3,13 +3,22 @@ use std::path::{Path};
 
 
 pub fn store<T, P>(data: &T, path: P)
+where
+    T: Serialize,
+    P: AsRef<Path>,
+{
+    store_to(data, Path::new("cache").join(path))
+}
+
+/// Like `store`, but `file` is not relative to the cache directory.
+pub fn store_to<T, P>(data: &T, file: P)
 where
     T: Serialize,
     P: AsRef<Path>,
 {
     let config = ron::ser::PrettyConfig::new()
         .with_depth_limit(4);
-    let file = Path::new("cache").join(path);
+    let file = file.as_ref();
     std::fs::create_dir_all(&file.parent().unwrap()).unwrap();
     let file = std::fs::File::create(file).unwrap();
     ron::ser::to_writer_pretty(std::io::BufWriter::new(file),
@@ -23,6 +32,29 @@ where
     P: AsRef<Path>,
     T: serde::de::DeserializeOwned,
 {
-    let file = std::fs::File::open(path).unwrap();
-    ron::de::from_reader(std::io::BufReader::new(file)).unwrap()
-}
\ No newline at end of file
+    read(path).unwrap()
+}
+
+pub fn read<T, P>(path: P) -> Result<T, String>
+where
+    P: AsRef<Path>,
+    T: serde::de::DeserializeOwned,
+{
+    let path = path.as_ref();
+    let file = std::fs::File::open(path)
+        .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+    ron::de::from_reader(std::io::BufReader::new(file))
+        .map_err(|err| format!("Failed to parse {}: {}", path.display(), err))
+}
+
+pub fn try_load<T, P>(path: P) -> Option<T>
+where
+    P: AsRef<Path>,
+    T: serde::de::DeserializeOwned,
+{
+    if path.as_ref().exists() {
+        Some(load(path))
+    } else {
+        None
+    }
+}